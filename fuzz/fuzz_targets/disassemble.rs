@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::io;
+
+// the first two bytes give the length of the ROM, the rest being the CDL
+fuzz_target!(|data: &[u8]| {
+    let Some((length, data)) = data.split_first_chunk::<2>() else {
+        return;
+    };
+    let length = (u16::from_le_bytes(*length) as usize).min(data.len());
+    let (rom, cdl) = data.split_at(length);
+
+    let options = nes_disasm::Options::new();
+    let _ = nes_disasm::disassemble_to_writer(rom, cdl, &options, &mut io::sink(), |_| {
+        Ok(Box::new(io::sink()))
+    });
+});
@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Write;
+use std::rc::Rc;
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+/// The archives `--output` can write to, instead of a directory.
+#[derive(Copy, Clone)]
+pub(crate) enum Format {
+    Zip,
+    Tar,
+}
+
+impl Format {
+    pub(crate) fn from_path(path: &str) -> Option<Format> {
+        let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "zip" => Some(Format::Zip),
+            "tar" => Some(Format::Tar),
+            _ => None,
+        }
+    }
+}
+
+/// A file kept in memory until the archive is written.
+#[derive(Clone, Default)]
+pub(crate) struct MemoryFile(Rc<RefCell<Vec<u8>>>);
+
+impl Write for MemoryFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The files of the disassembly, in the order they were created.
+#[derive(Clone, Default)]
+pub(crate) struct Files(Rc<RefCell<Vec<(String, MemoryFile)>>>);
+
+impl Files {
+    pub(crate) fn create(&self, name: &str) -> MemoryFile {
+        let file = MemoryFile::default();
+        self.0.borrow_mut().push((name.to_string(), file.clone()));
+        file
+    }
+
+    /// The name and the size of every file.
+    pub(crate) fn sizes(&self) -> Vec<(String, u64)> {
+        self.0
+            .borrow()
+            .iter()
+            .map(|(name, file)| (name.clone(), file.0.borrow().len() as u64))
+            .collect()
+    }
+
+    /// The name and the text of every file.
+    #[cfg(test)]
+    pub(crate) fn texts(&self) -> Vec<(String, String)> {
+        self.0
+            .borrow()
+            .iter()
+            .map(|(name, file)| {
+                (
+                    name.clone(),
+                    String::from_utf8_lossy(&file.0.borrow()).into_owned(),
+                )
+            })
+            .collect()
+    }
+
+    /// Writes every file to the archive `path`.
+    pub(crate) fn write(&self, path: &str, format: Format) -> std::io::Result<()> {
+        let output = File::create(path)?;
+        let files = self.0.borrow();
+        match format {
+            Format::Zip => {
+                let mut zip = ZipWriter::new(output);
+                for (name, file) in files.iter() {
+                    zip.start_file(name.as_str(), SimpleFileOptions::default())?;
+                    zip.write_all(&file.0.borrow())?;
+                }
+                zip.finish()?;
+            }
+            Format::Tar => {
+                let mut tar = tar::Builder::new(output);
+                for (name, file) in files.iter() {
+                    let data = file.0.borrow();
+                    let mut header = tar::Header::new_gnu();
+                    header.set_size(data.len() as u64);
+                    header.set_mode(0o644);
+                    tar.append_data(&mut header, name, &data[..])?;
+                }
+                tar.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
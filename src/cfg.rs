@@ -0,0 +1,138 @@
+use crate::Addressing;
+use crate::DataLabels;
+use crate::Instruction;
+use crate::Options;
+use crate::RomData;
+use crate::decode_one;
+use crate::prg_offset;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::io::Write;
+
+enum Edge {
+    Fallthrough,
+    Branch,
+    Jump,
+}
+
+impl Edge {
+    fn name(&self) -> &'static str {
+        match self {
+            Edge::Fallthrough => "fallthrough",
+            Edge::Branch => "branch",
+            Edge::Jump => "jump",
+        }
+    }
+}
+
+/// Writes, in the DOT format, the basic blocks reachable from `root` without
+/// following the subroutine calls.
+pub(crate) fn write_cfg(
+    root: usize,
+    prg: &[u8],
+    cdl: &[u8],
+    rom_data: RomData,
+    data_labels: DataLabels,
+    options: &Options,
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    let mut instructions = BTreeMap::new();
+    let mut leaders = BTreeSet::from([root]);
+
+    let mut roots = vec![root];
+    while let Some(mut position) = roots.pop() {
+        while !instructions.contains_key(&position) {
+            let Some(offset) = prg_offset(position, rom_data) else {
+                break;
+            };
+            if (cdl[offset] & 3) == 2 {
+                break;
+            }
+            let Some(instruction) = decode_one(prg, position, rom_data, data_labels, options)
+            else {
+                break;
+            };
+
+            let next = instruction.next();
+            let target = instruction.rom_target();
+            let ends = ends_block(&instruction);
+            let falls_through = falls_through(&instruction);
+            instructions.insert(position, instruction);
+
+            if let Some(target) = target.filter(|_| ends) {
+                leaders.insert(target);
+                roots.push(target);
+            }
+            if ends {
+                leaders.insert(next);
+            }
+            if !falls_through {
+                break;
+            }
+            position = next;
+        }
+    }
+
+    writeln!(output, "digraph L{root:06X} {{")?;
+    writeln!(output, "    node [shape=box fontname=\"monospace\"];")?;
+
+    let mut block = None;
+    let mut text = String::new();
+    for (position, instruction) in &instructions {
+        let block_start = *block.get_or_insert(*position);
+        if text.is_empty() {
+            text = format!("L{block_start:06X}:\\l");
+        }
+        text += &format!(
+            "    {} {}\\l",
+            instruction.opcode.name,
+            instruction.operand.replace('"', "\\\"")
+        );
+
+        let next = instruction.next();
+        let ends = ends_block(instruction) || leaders.contains(&next);
+        if !ends && instructions.contains_key(&next) {
+            continue;
+        }
+
+        writeln!(output, "    L{block_start:06X} [label=\"{text}\"];")?;
+
+        let mut edges = vec![];
+        if let Some(target) = instruction.rom_target().filter(|_| ends_block(instruction)) {
+            let edge = if matches!(instruction.opcode.addressing, Addressing::Relative) {
+                Edge::Branch
+            } else {
+                Edge::Jump
+            };
+            edges.push((target, edge));
+        }
+        if falls_through(instruction) && instructions.contains_key(&next) {
+            edges.push((next, Edge::Fallthrough));
+        }
+        for (target, edge) in edges {
+            writeln!(
+                output,
+                "    L{block_start:06X} -> L{target:06X} [label=\"{}\"];",
+                edge.name()
+            )?;
+        }
+
+        block = None;
+        text.clear();
+    }
+
+    writeln!(output, "}}")
+}
+
+/// Branches, jumps and returns end a basic block, subroutine calls don't.
+fn ends_block(instruction: &Instruction) -> bool {
+    matches!(instruction.opcode.addressing, Addressing::Relative)
+        || matches!(instruction.opcode.name, "JMP" | "RTS" | "RTI" | "BRK")
+}
+
+fn falls_through(instruction: &Instruction) -> bool {
+    !matches!(
+        instruction.opcode.name,
+        "JMP" | "RTS" | "RTI" | "BRK" | "BRA"
+    )
+}
@@ -0,0 +1,84 @@
+use crate::Palette;
+use std::io::Write;
+
+const TILE_SIZE: usize = 16;
+const TILES_PER_ROW: usize = 16;
+/// tiles in a 8KB CHR bank
+const CHR_TILES: usize = 512;
+
+/// An image made of 8x8 tiles, each pixel being a 2-bit color index.
+pub(crate) struct Sheet {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Sheet {
+    /// A blank sheet of `columns` by `rows` tiles.
+    pub(crate) fn new(columns: usize, rows: usize) -> Self {
+        let width = columns * 8;
+        let height = rows * 8;
+        Sheet {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    /// A CHR bank, as 16 tiles per row.
+    pub(crate) fn from_bank(bank: &[u8]) -> Self {
+        let tiles = bank.len() / TILE_SIZE;
+        let mut sheet = Sheet::new(TILES_PER_ROW, tiles.div_ceil(TILES_PER_ROW));
+        for (index, tile) in bank.chunks_exact(TILE_SIZE).enumerate() {
+            sheet.draw_tile(index % TILES_PER_ROW, index / TILES_PER_ROW, tile);
+        }
+        sheet
+    }
+
+    /// Every bank one after the other, separated by a blank row of tiles.
+    pub(crate) fn from_banks(banks: &[Vec<u8>], tiles_per_row: usize) -> Self {
+        let tiles_per_row = tiles_per_row.max(1);
+        let rows_per_bank = CHR_TILES.div_ceil(tiles_per_row);
+        let rows = banks.len() * (rows_per_bank + 1) - 1;
+        let mut sheet = Sheet::new(tiles_per_row, rows);
+        for (id, bank) in banks.iter().enumerate() {
+            let first_row = id * (rows_per_bank + 1);
+            for (index, tile) in bank.chunks_exact(TILE_SIZE).enumerate() {
+                sheet.draw_tile(
+                    index % tiles_per_row,
+                    first_row + index / tiles_per_row,
+                    tile,
+                );
+            }
+        }
+        sheet
+    }
+
+    /// Draws a 2bpp tile: the low bits of its 8 rows, then the high bits.
+    pub(crate) fn draw_tile(&mut self, column: usize, row: usize, tile: &[u8]) {
+        for y in 0..8 {
+            let (low, high) = (tile[y], tile[y + 8]);
+            for x in 0..8 {
+                let shift = 7 - x;
+                let color = ((low >> shift) & 1) | (((high >> shift) & 1) << 1);
+                self.pixels[(row * 8 + y) * self.width + column * 8 + x] = color;
+            }
+        }
+    }
+
+    /// Writes the sheet as an indexed PNG.
+    pub(crate) fn write_png(
+        &self,
+        palette: &Palette,
+        output: impl Write,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut encoder = png::Encoder::new(output, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(palette.0.concat());
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
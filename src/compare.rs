@@ -0,0 +1,393 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// lines of context around the changes
+const CONTEXT: usize = 3;
+/// beyond this many changes in a file, the changed part is shown as replaced
+/// as a whole instead of compared line by line
+const MAX_CHANGES: usize = 2000;
+
+/// Writes the unified diff of the assembly files (`.s` and `.asm`) of two
+/// output directories, the addresses of the labels being left out so only
+/// the changes of structure show. Returns whether they differ.
+pub fn compare(
+    first: &str,
+    second: &str,
+    output: &mut impl Write,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut names = assembly_files(first)?;
+    names.append(&mut assembly_files(second)?);
+
+    let mut differ = false;
+    for name in names {
+        let (a, b) = (Path::new(first).join(&name), Path::new(second).join(&name));
+        if !a.exists() || !b.exists() {
+            let (only, path) = if a.exists() { (first, a) } else { (second, b) };
+            log::debug!("{} is only in {only}", path.display());
+            writeln!(output, "Only in {only}: {name}")?;
+            differ = true;
+            continue;
+        }
+
+        let (a_text, b_text) = (fs::read_to_string(&a)?, fs::read_to_string(&b)?);
+        let a_lines: Vec<String> = a_text.lines().map(normalize).collect();
+        let b_lines: Vec<String> = b_text.lines().map(normalize).collect();
+        let edits = diff(&a_lines, &b_lines);
+        if edits.iter().all(|edit| matches!(edit, Edit::Same(..))) {
+            continue;
+        }
+
+        differ = true;
+        writeln!(output, "--- {}", a.display())?;
+        writeln!(output, "+++ {}", b.display())?;
+        write_hunks(&edits, &a_lines, &b_lines, output)?;
+    }
+    Ok(differ)
+}
+
+/// The names of the assembly files of `dir`.
+fn assembly_files(dir: &str) -> std::io::Result<BTreeSet<String>> {
+    let mut names = BTreeSet::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if matches!(path.extension().and_then(|e| e.to_str()), Some("s" | "asm"))
+            && let Some(name) = path.file_name().and_then(|n| n.to_str())
+        {
+            names.insert(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+/// Replaces the address of the labels (`L01C010`, `_L01C010`) with `x`s.
+fn normalize(line: &str) -> String {
+    let bytes = line.as_bytes();
+    let mut normalized = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < line.len() {
+        let starts_word = i == 0 || !bytes[i - 1].is_ascii_alphanumeric();
+        let is_label = starts_word
+            && bytes[i] == b'L'
+            && bytes.len() >= i + 7
+            && bytes[i + 1..i + 7].iter().all(u8::is_ascii_hexdigit)
+            && bytes.get(i + 7).is_none_or(|b| !b.is_ascii_alphanumeric());
+        if is_label {
+            normalized.push_str("Lxxxxxx");
+            i += 7;
+        } else {
+            let c = line[i..].chars().next().unwrap_or_default();
+            normalized.push(c);
+            i += c.len_utf8();
+        }
+    }
+    normalized
+}
+
+/// A line kept, removed from the first file or added by the second, with its
+/// index in each file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Edit {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// The edits from `a` to `b`, the shortest ones found by Myers' algorithm
+/// once the common start and end are set apart.
+fn diff(a: &[String], b: &[String]) -> Vec<Edit> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_end, b_end) = (a.len() - suffix, b.len() - suffix);
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|i| Edit::Same(i, i)).collect();
+    match shortest_edits(&a[prefix..a_end], &b[prefix..b_end]) {
+        Some(middle) => edits.extend(middle.into_iter().map(|edit| match edit {
+            Edit::Same(i, j) => Edit::Same(prefix + i, prefix + j),
+            Edit::Removed(i) => Edit::Removed(prefix + i),
+            Edit::Added(j) => Edit::Added(prefix + j),
+        })),
+        None => {
+            log::warn!("More than {MAX_CHANGES} changes, shown as replaced.");
+            edits.extend((prefix..a_end).map(Edit::Removed));
+            edits.extend((prefix..b_end).map(Edit::Added));
+        }
+    }
+    edits.extend((0..suffix).map(|k| Edit::Same(a_end + k, b_end + k)));
+    edits
+}
+
+/// Myers' algorithm: for each number of changes `d`, the furthest line of
+/// `a` reached on every diagonal `k` (the line of `a` minus the one of `b`),
+/// then back from the end through the diagonals kept. `None` beyond
+/// `MAX_CHANGES`.
+fn shortest_edits(a: &[String], b: &[String]) -> Option<Vec<Edit>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (a.len() + b.len()).min(MAX_CHANGES) as isize;
+    let mut furthest = vec![0isize; 2 * max as usize + 3];
+    let at = |k: isize| (k + max + 1) as usize;
+    // the diagonals before each step
+    let mut trace = vec![];
+    'search: for d in 0..=max {
+        trace.push(furthest[at(-d)..=at(d)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && furthest[at(k - 1)] < furthest[at(k + 1)]) {
+                furthest[at(k + 1)]
+            } else {
+                furthest[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[at(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+        if d == max {
+            return None;
+        }
+    }
+
+    let mut edits = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, previous) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        if d == 0 {
+            while x > 0 && y > 0 {
+                edits.push(Edit::Same(x as usize - 1, y as usize - 1));
+                x -= 1;
+                y -= 1;
+            }
+            break;
+        }
+        let get = |k: isize| previous[(k + d) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = get(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            edits.push(Edit::Same(x as usize - 1, y as usize - 1));
+            x -= 1;
+            y -= 1;
+        }
+        if x == previous_x {
+            edits.push(Edit::Added(y as usize - 1));
+        } else {
+            edits.push(Edit::Removed(x as usize - 1));
+        }
+        (x, y) = (previous_x, previous_y);
+    }
+    edits.reverse();
+    Some(edits)
+}
+
+/// Writes the changes, `CONTEXT` lines around them, as `@@ -a,n +b,m @@`
+/// hunks.
+fn write_hunks(
+    edits: &[Edit],
+    a: &[String],
+    b: &[String],
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|k| !matches!(edits[*k], Edit::Same(..)))
+        .collect();
+    let mut k = 0;
+    while k < changes.len() {
+        // the changes closer than twice the context make a single hunk
+        let mut last = k;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT + 1 {
+            last += 1;
+        }
+        let start = changes[k].saturating_sub(CONTEXT);
+        let end = (changes[last] + CONTEXT + 1).min(edits.len());
+        let hunk = &edits[start..end];
+
+        // the position of the hunk in each file, from the edits before it
+        let a_start = edits[..start]
+            .iter()
+            .filter(|e| !matches!(e, Edit::Added(_)))
+            .count();
+        let b_start = edits[..start]
+            .iter()
+            .filter(|e| !matches!(e, Edit::Removed(_)))
+            .count();
+        let a_len = hunk.iter().filter(|e| !matches!(e, Edit::Added(_))).count();
+        let b_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Removed(_)))
+            .count();
+        writeln!(
+            output,
+            "@@ -{},{a_len} +{},{b_len} @@",
+            a_start + 1,
+            b_start + 1
+        )?;
+        for edit in hunk {
+            match edit {
+                Edit::Same(i, _) => writeln!(output, " {}", a[*i])?,
+                Edit::Removed(i) => writeln!(output, "-{}", a[*i])?,
+                Edit::Added(j) => writeln!(output, "+{}", b[*j])?,
+            }
+        }
+        k = last + 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split(' ').map(String::from).collect()
+    }
+
+    /// The hunks of the diff of `a` and `b`.
+    fn hunks(a: &[String], b: &[String]) -> String {
+        let mut output = vec![];
+        write_hunks(&diff(a, b), a, b, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn insert() {
+        let edits = diff(&lines("a b"), &lines("a x b"));
+        assert_eq!(edits, [Edit::Same(0, 0), Edit::Added(1), Edit::Same(1, 2)]);
+    }
+
+    #[test]
+    fn delete() {
+        let edits = diff(&lines("a b c"), &lines("a c"));
+        assert_eq!(
+            edits,
+            [Edit::Same(0, 0), Edit::Removed(1), Edit::Same(2, 1)]
+        );
+    }
+
+    #[test]
+    fn replace() {
+        let edits = diff(&lines("a b c"), &lines("a x c"));
+        assert_eq!(
+            edits,
+            [
+                Edit::Same(0, 0),
+                Edit::Removed(1),
+                Edit::Added(1),
+                Edit::Same(2, 2)
+            ]
+        );
+        assert_eq!(
+            hunks(&lines("a b c"), &lines("a x c")),
+            "@@ -1,3 +1,3 @@\n a\n-b\n+x\n c\n"
+        );
+    }
+
+    #[test]
+    fn shortest_edits_in_the_middle() {
+        // the example of Myers' paper, 5 changes
+        let (a, b) = (lines("A B C A B B A"), lines("C B A B A C"));
+        let edits = diff(&a, &b);
+        let changes = edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Same(..)))
+            .count();
+        assert_eq!(changes, 5);
+        // the lines kept and added give the second file back
+        let rebuilt: Vec<_> = edits
+            .iter()
+            .filter_map(|edit| match edit {
+                Edit::Same(i, _) => Some(&a[*i]),
+                Edit::Added(j) => Some(&b[*j]),
+                Edit::Removed(_) => None,
+            })
+            .collect();
+        assert_eq!(rebuilt, b.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn hunk_merging() {
+        let a: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+
+        // 5 lines apart, their context overlaps
+        let mut b = a.clone();
+        b[5] = "x".into();
+        b[11] = "y".into();
+        let text = hunks(&a, &b);
+        assert_eq!(text.matches("@@ -").count(), 1, "{text}");
+        assert!(text.starts_with("@@ -3,13 +3,13 @@\n 2\n"), "{text}");
+
+        // 12 lines apart, two hunks
+        let mut b = a.clone();
+        b[2] = "x".into();
+        b[15] = "y".into();
+        let text = hunks(&a, &b);
+        assert_eq!(text.matches("@@ -").count(), 2, "{text}");
+        assert!(text.contains("@@ -13,7 +13,7 @@\n 12\n"), "{text}");
+    }
+
+    #[test]
+    fn too_many_changes() {
+        // every line replaced, more changes than MAX_CHANGES
+        let count = MAX_CHANGES / 2 + 1;
+        let a: Vec<String> = (0..count).map(|i| format!("a{i}")).collect();
+        let b: Vec<String> = (0..count).map(|i| format!("b{i}")).collect();
+        let mut a_with_ends = lines("start");
+        a_with_ends.extend(a);
+        a_with_ends.push("end".into());
+        let mut b_with_ends = lines("start");
+        b_with_ends.extend(b);
+        b_with_ends.push("end".into());
+
+        let edits = diff(&a_with_ends, &b_with_ends);
+        let mut expected = vec![Edit::Same(0, 0)];
+        expected.extend((1..=count).map(Edit::Removed));
+        expected.extend((1..=count).map(Edit::Added));
+        expected.push(Edit::Same(count + 1, count + 1));
+        assert_eq!(edits, expected);
+    }
+
+    #[test]
+    fn label_addresses_left_out() {
+        assert_eq!(normalize("    JSR L01C010.w"), "    JSR Lxxxxxx.w");
+        assert_eq!(normalize("_L008000:"), "_Lxxxxxx:");
+        assert_eq!(normalize("    LDA NOTL01C0100"), "    LDA NOTL01C0100");
+    }
+
+    #[test]
+    fn directories_differ() {
+        let root = std::env::temp_dir().join(format!("nes-disasm-compare-{}", std::process::id()));
+        let (first, second) = (root.join("first"), root.join("second"));
+        for dir in [&first, &second] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(first.join("main.s"), "L01C000:\n    RTS\n").unwrap();
+        fs::write(second.join("main.s"), "L01C010:\n    RTS\n").unwrap();
+        let (first, second) = (first.to_str().unwrap(), second.to_str().unwrap());
+
+        // only the addresses of the labels change
+        let mut output = vec![];
+        assert!(!compare(first, second, &mut output).unwrap());
+        assert!(output.is_empty());
+
+        fs::write(Path::new(second).join("main.s"), "L01C010:\n    RTI\n").unwrap();
+        assert!(compare(first, second, &mut output).unwrap());
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.contains("-    RTS\n+    RTI\n"), "{text}");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
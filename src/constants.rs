@@ -0,0 +1,128 @@
+use crate::Addressing;
+use crate::DataLabels;
+use crate::Options;
+use crate::RomData;
+use crate::decode_one;
+use crate::offset_to_label;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+/// A value used as an operand, which can be given a name with `.DEFINE`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Constant {
+    Immediate(u8),
+    ZeroPage(u8),
+}
+
+impl Constant {
+    pub(crate) fn new(addressing: &Addressing, value: u8) -> Option<Constant> {
+        match addressing {
+            Addressing::Immediate => Some(Constant::Immediate(value)),
+            Addressing::ZeroPage | Addressing::ZeroPageX | Addressing::ZeroPageY => {
+                Some(Constant::ZeroPage(value))
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(&self) -> String {
+        match self {
+            Constant::Immediate(value) => format!("imm_{value:02X}"),
+            Constant::ZeroPage(value) => format!("zp_{value:02X}"),
+        }
+    }
+
+    pub(crate) fn value(&self) -> u8 {
+        match self {
+            Constant::Immediate(value) | Constant::ZeroPage(value) => *value,
+        }
+    }
+
+    /// Returns the operand using the name instead of the value.
+    pub(crate) fn operand(&self, addressing: &Addressing, options: &Options) -> String {
+        let name = self.name();
+        match addressing {
+            Addressing::Immediate => format!("#{name}"),
+            Addressing::ZeroPageX => format!("{},X", options.zero_page(name)),
+            Addressing::ZeroPageY => format!("{},Y", options.zero_page(name)),
+            _ => options.zero_page(name),
+        }
+    }
+}
+
+/// Returns the constants used by the code at least `threshold` times.
+pub(crate) fn find_constants(
+    prg: &[u8],
+    cdl: &[u8],
+    rom_data: RomData,
+    options: &Options,
+    threshold: usize,
+) -> Vec<Constant> {
+    let mut counts = BTreeMap::new();
+
+    let mut i = 0;
+    while i < prg.len() {
+        if (cdl[i] & 1) == 1 {
+            let position = offset_to_label(i, rom_data);
+            if let Some(instruction) =
+                decode_one(prg, position, rom_data, DataLabels::default(), options)
+            {
+                if let Some(constant) =
+                    Constant::new(&instruction.opcode.addressing, instruction.value as u8)
+                {
+                    *counts.entry(constant).or_insert(0) += 1;
+                }
+
+                // same as the disassembly, an instruction can't overlap two banks
+                i += instruction
+                    .size
+                    .min(rom_data.bank_size - 1 - i % rom_data.bank_size);
+            }
+        }
+
+        i += 1;
+    }
+
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count >= threshold)
+        .map(|(constant, _)| constant)
+        .collect()
+}
+
+/// Returns the zero-page addresses used as pointers by `(zp),Y`, the
+/// pointers being the 2 bytes from there.
+pub(crate) fn find_zero_page_pointers(
+    prg: &[u8],
+    cdl: &[u8],
+    rom_data: RomData,
+    options: &Options,
+) -> BTreeSet<u8> {
+    let mut pointers = BTreeSet::new();
+
+    let mut i = 0;
+    while i < prg.len() {
+        if (cdl[i] & 1) == 1 {
+            let position = offset_to_label(i, rom_data);
+            if let Some(instruction) =
+                decode_one(prg, position, rom_data, DataLabels::default(), options)
+            {
+                if instruction.opcode.addressing == Addressing::IndirectY {
+                    pointers.insert(instruction.value as u8);
+                }
+
+                i += instruction
+                    .size
+                    .min(rom_data.bank_size - 1 - i % rom_data.bank_size);
+            }
+        }
+
+        i += 1;
+    }
+
+    pointers
+}
+
+pub(crate) fn pointer_name(addr: u8) -> String {
+    format!("ptr_{addr:02X}")
+}
@@ -0,0 +1,39 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum DisasmError {
+    UnsupportedMapper(u8),
+    CdlLengthMismatch {
+        expected: usize,
+        found: usize,
+    },
+    /// the header declares no PRG bank, or the file is shorter than one
+    NoPrgBanks,
+    /// `--bank-size` isn't 8, 16 or 32KB, or doesn't divide the PRG
+    BankSize {
+        bank_size: usize,
+        prg_size: usize,
+    },
+}
+
+impl fmt::Display for DisasmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DisasmError::UnsupportedMapper(mapper) => write!(f, "Unsupported mapper: {mapper}"),
+            DisasmError::CdlLengthMismatch { expected, found } => write!(
+                f,
+                "The CDL files have different lengths ({expected} and {found} bytes)"
+            ),
+            DisasmError::NoPrgBanks => write!(f, "The ROM has no PRG bank"),
+            DisasmError::BankSize {
+                bank_size,
+                prg_size,
+            } => write!(
+                f,
+                "The PRG of {prg_size} bytes can't be split in banks of {bank_size} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DisasmError {}
@@ -0,0 +1,348 @@
+use byteorder::ReadBytesExt;
+use std::fmt;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    FourScreen,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TvSystem {
+    Ntsc,
+    Pal,
+    /// NES 2.0 only, the game runs on both
+    MultiRegion,
+    /// NES 2.0 only, the timing of the Dendy famiclones
+    Dendy,
+}
+
+impl fmt::Display for TvSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TvSystem::Ntsc => write!(f, "NTSC"),
+            TvSystem::Pal => write!(f, "PAL"),
+            TvSystem::MultiRegion => write!(f, "multi-region"),
+            TvSystem::Dendy => write!(f, "Dendy"),
+        }
+    }
+}
+
+/// The 16-byte iNES header.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Header {
+    pub magic: [u8; 4],
+    pub prg_banks_count: u8,
+    pub chr_banks_count: u8,
+    pub flags_06: u8,
+    pub flags_07: u8,
+    pub mapper: u8,
+    pub mirroring: Mirroring,
+    pub battery: bool,
+    pub trainer: bool,
+    /// from byte 9, or the CPU/PPU timing of byte 12 in the NES 2.0 format.
+    pub tv_system: TvSystem,
+    /// from byte 10, whose bit 4 is set when there's no PRG-RAM at `$6000`.
+    /// In the NES 2.0 format, byte 10 holds the shift counts of the PRG-RAM
+    /// (low nibble) and of the PRG-NVRAM (high nibble), 0 meaning none.
+    pub prg_ram: bool,
+    /// the NES 2.0 format, flagged by the bits 2-3 of byte 7
+    pub nes2: bool,
+    /// from byte 8, only in the NES 2.0 format
+    pub submapper: Option<u8>,
+    /// from byte 14, the number of miscellaneous ROMs stored after the CHR,
+    /// only in the NES 2.0 format
+    pub misc_roms: u8,
+    /// bytes 8 to 15, kept as-is.
+    pub padding: [u8; 8],
+}
+
+impl Header {
+    pub fn read(rom: &mut impl Read) -> Result<Header, Box<dyn std::error::Error>> {
+        let mut magic = [0u8; 4];
+        rom.read_exact(&mut magic)?;
+        if magic != *b"NES\x1A" {
+            return Err(Box::new(Error::new(
+                ErrorKind::InvalidInput,
+                "This file is not an iNES ROM.",
+            )));
+        }
+
+        let prg_banks_count = rom.read_u8()?;
+        let chr_banks_count = rom.read_u8()?;
+        let flags_06 = rom.read_u8()?;
+        let flags_07 = rom.read_u8()?;
+        let mut padding = [0u8; 8];
+        rom.read_exact(&mut padding)?;
+
+        if let Some(tail) = ascii_tail(flags_07, &padding) {
+            log::warn!("The header ends with \"{tail}\", the mapper number might be wrong.");
+        }
+
+        let mirroring = if flags_06 & 0x08 != 0 {
+            Mirroring::FourScreen
+        } else if flags_06 & 0x01 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+
+        let nes2 = flags_07 & 0x0C == 0x08;
+
+        Ok(Header {
+            magic,
+            prg_banks_count,
+            chr_banks_count,
+            flags_06,
+            flags_07,
+            mapper: (flags_07 & 0xF0) | (flags_06 >> 4),
+            mirroring,
+            battery: flags_06 & 0x02 != 0,
+            trainer: flags_06 & 0x04 != 0,
+            tv_system: match (nes2, padding[4] & 0x03) {
+                (true, 0) => TvSystem::Ntsc,
+                (true, 1) => TvSystem::Pal,
+                (true, 2) => TvSystem::MultiRegion,
+                (true, _) => TvSystem::Dendy,
+                (false, _) if padding[1] & 0x01 != 0 => TvSystem::Pal,
+                (false, _) => TvSystem::Ntsc,
+            },
+            prg_ram: if nes2 {
+                padding[2] != 0
+            } else {
+                padding[2] & 0x10 == 0
+            },
+            nes2,
+            submapper: nes2.then_some(padding[0] >> 4),
+            misc_roms: if nes2 { padding[6] & 0x03 } else { 0 },
+            padding,
+        })
+    }
+
+    /// The size of the PRG ROM in bytes, byte 4 counting 16KB units. In the
+    /// NES 2.0 format, the low nibble of byte 9 holds the most significant
+    /// bits, or flags the exponent-multiplier notation when it is `$F`.
+    pub fn prg_rom_size(&self) -> usize {
+        rom_size(
+            self.prg_banks_count,
+            self.padding[1] & 0x0F,
+            self.nes2,
+            0x4000,
+        )
+    }
+
+    /// The size of the CHR ROM in bytes, byte 5 counting 8KB units, with the
+    /// high nibble of byte 9 in the NES 2.0 format.
+    pub fn chr_rom_size(&self) -> usize {
+        rom_size(
+            self.chr_banks_count,
+            self.padding[1] >> 4,
+            self.nes2,
+            0x2000,
+        )
+    }
+
+    /// The 16 bytes of the header, as they were read.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[..4].copy_from_slice(&self.magic);
+        bytes[4] = self.prg_banks_count;
+        bytes[5] = self.chr_banks_count;
+        bytes[6] = self.flags_06;
+        bytes[7] = self.flags_07;
+        bytes[8..].copy_from_slice(&self.padding);
+        bytes
+    }
+}
+
+/// The text old dumping tools (\"DiskDude!\") wrote over the bytes 7 to 15
+/// of the header, if they are all ASCII.
+fn ascii_tail(flags_07: u8, padding: &[u8; 8]) -> Option<String> {
+    let tail = [&[flags_07][..], padding].concat();
+    (padding.iter().any(|&b| b != 0) && tail.iter().all(|b| b.is_ascii_graphic()))
+        .then(|| String::from_utf8_lossy(&tail).into_owned())
+}
+
+/// The size of a ROM from its count of `unit`s and, in the NES 2.0 format,
+/// the nibble of byte 9: `2^E * (MM * 2 + 1)` bytes (`EEEEEEMM` in the count)
+/// when it is `$F`, the most significant bits of the count otherwise.
+fn rom_size(count: u8, msb: u8, nes2: bool, unit: usize) -> usize {
+    match (nes2, msb) {
+        (true, 0x0F) => {
+            let exponent = u32::from(count >> 2).min(usize::BITS - 4);
+            (1usize << exponent) * ((count & 0x03) as usize * 2 + 1)
+        }
+        (true, _) => ((msb as usize) << 8 | count as usize) * unit,
+        (false, _) => count as usize * unit,
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+        let format = if self.nes2 { "NES 2.0" } else { "iNES" };
+        writeln!(f, "format:     {format}")?;
+        writeln!(f, "mapper:     {}", self.mapper)?;
+        match self.submapper {
+            Some(submapper) => writeln!(f, "submapper:  {submapper}")?,
+            None => writeln!(f, "submapper:  -")?,
+        }
+        let (prg_size, chr_size) = (self.prg_rom_size(), self.chr_rom_size());
+        writeln!(
+            f,
+            "PRG ROM:    {} x 16KB ({prg_size} bytes)",
+            prg_size / 0x4000
+        )?;
+        writeln!(
+            f,
+            "CHR ROM:    {} x 8KB ({chr_size} bytes)",
+            chr_size / 0x2000
+        )?;
+        writeln!(f, "mirroring:  {:?}", self.mirroring)?;
+        writeln!(f, "battery:    {}", yes_no(self.battery))?;
+        writeln!(f, "trainer:    {}", yes_no(self.trainer))?;
+        writeln!(f, "TV system:  {}", self.tv_system)?;
+        writeln!(f, "PRG RAM:    {}", yes_no(self.prg_ram))?;
+        write!(f, "misc ROMs:  {}", self.misc_roms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(bytes: [u8; 16]) -> Header {
+        Header::read(&mut &bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn ines_header() {
+        let header = read(*b"NES\x1A\x02\x01\x13\x00\x00\x00\x00\x00\x00\x00\x00\x00");
+        assert!(!header.nes2);
+        assert_eq!(header.mapper, 1);
+        assert_eq!(header.mirroring, Mirroring::Vertical);
+        assert!(header.battery);
+        assert!(!header.trainer);
+        assert_eq!(header.tv_system, TvSystem::Ntsc);
+        assert!(header.prg_ram);
+        assert_eq!(header.submapper, None);
+        assert_eq!(header.prg_rom_size(), 0x8000);
+        assert_eq!(header.chr_rom_size(), 0x2000);
+    }
+
+    #[test]
+    fn ines_header_without_prg_ram() {
+        let header = read(*b"NES\x1A\x01\x01\x00\x00\x00\x00\x10\x00\x00\x00\x00\x00");
+        assert!(!header.prg_ram);
+    }
+
+    #[test]
+    fn ines_tv_system() {
+        let ntsc = read(*b"NES\x1A\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00");
+        assert_eq!(ntsc.tv_system, TvSystem::Ntsc);
+        let pal = read(*b"NES\x1A\x01\x01\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00");
+        assert_eq!(pal.tv_system, TvSystem::Pal);
+        assert_eq!(pal.tv_system.to_string(), "PAL");
+    }
+
+    #[test]
+    fn nes2_header() {
+        // byte 9: MSBs of the sizes, byte 10: 8KB of PRG-NVRAM, byte 12: PAL
+        let header = read(*b"NES\x1A\x02\x01\x02\x08\x31\x21\x70\x00\x01\x00\x00\x00");
+        assert!(header.nes2);
+        assert_eq!(header.submapper, Some(3));
+        assert_eq!(header.tv_system, TvSystem::Pal);
+        assert!(header.battery);
+        assert!(header.prg_ram);
+        assert_eq!(header.prg_rom_size(), 0x102 * 0x4000);
+        assert_eq!(header.chr_rom_size(), 0x201 * 0x2000);
+        let text = header.to_string();
+        assert!(
+            text.contains("PRG ROM:    258 x 16KB (4227072 bytes)"),
+            "{text}"
+        );
+        assert!(
+            text.contains("CHR ROM:    513 x 8KB (4202496 bytes)"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn nes2_header_ignores_the_ines_bytes() {
+        // byte 9 bit 0 and byte 10 bit 4 mean something else in NES 2.0
+        let header = read(*b"NES\x1A\x02\x01\x00\x08\x00\x01\x10\x00\x00\x00\x00\x00");
+        assert_eq!(header.tv_system, TvSystem::Ntsc);
+        assert!(header.prg_ram);
+        assert_eq!(header.prg_rom_size(), 0x102 * 0x4000);
+
+        let header = read(*b"NES\x1A\x02\x01\x00\x08\x00\x00\x00\x00\x03\x00\x00\x00");
+        assert_eq!(header.tv_system, TvSystem::Dendy);
+        assert!(!header.prg_ram);
+    }
+
+    #[test]
+    fn nes2_exponent_multiplier_size() {
+        // 2^14 * (1 * 2 + 1) bytes of PRG
+        let header = read(*b"NES\x1A\x39\x00\x00\x08\x00\x0F\x00\x00\x00\x00\x00\x00");
+        assert_eq!(header.prg_rom_size(), 3 * 0x4000);
+    }
+
+    #[test]
+    fn round_trip() {
+        for bytes in [
+            *b"NES\x1A\x02\x01\x13\x00\x00\x01\x10\x00\x00\x00\x00\x00",
+            *b"NES\x1A\x20\x10\x4A\x18\x51\x21\x70\x07\x02\x00\x01\x05",
+        ] {
+            assert_eq!(read(bytes).to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn clean_magic() {
+        let bytes = *b"NES\x1A\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+        assert_eq!(read(bytes).magic, *b"NES\x1A");
+        assert_eq!(ascii_tail(bytes[7], bytes[8..].try_into().unwrap()), None);
+
+        let error =
+            Header::read(&mut &b"NES\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"[..]);
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn diskdude_header() {
+        let bytes = *b"NES\x1A\x02\x01\x40DiskDude!";
+        let header = read(bytes);
+        // the "D" is read as the high nibble of the mapper
+        assert_eq!(header.mapper, 0x44);
+        assert_eq!(
+            ascii_tail(bytes[7], bytes[8..].try_into().unwrap()).as_deref(),
+            Some("DiskDude!")
+        );
+    }
+
+    #[test]
+    fn header_fields() {
+        // NES 2.0, mapper 10 and submapper 5, vertical mirroring and a battery
+        let bytes = *b"NES\x1A\x02\x01\xA3\x08\x50\x00\x00\x00\x00\x00\x00\x00";
+        let fields = Header::read(&mut &bytes[..]).unwrap().to_string();
+        for field in [
+            "format:     NES 2.0",
+            "mapper:     10",
+            "submapper:  5",
+            "PRG ROM:    2 x 16KB (32768 bytes)",
+            "CHR ROM:    1 x 8KB (8192 bytes)",
+            "mirroring:  Vertical",
+            "battery:    yes",
+            "trainer:    no",
+        ] {
+            assert!(fields.lines().any(|line| line == field), "{fields}");
+        }
+    }
+}
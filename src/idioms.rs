@@ -0,0 +1,168 @@
+use crate::Addressing;
+use crate::Annotations;
+use crate::DataLabels;
+use crate::Options;
+use crate::RomData;
+use crate::cpu_to_label;
+use crate::decode_one;
+use crate::offset_to_label;
+use crate::prg_offset;
+use crate::registers;
+use crate::trace::read_word;
+
+const OAM_DMA: usize = 0x4014;
+const PPUMASK: usize = 0x2001;
+
+/// `--annotate-idioms`: comments the well-known sequences of instructions.
+pub(crate) fn annotate_idioms(
+    prg: &[u8],
+    cdl: &[u8],
+    rom_data: RomData,
+    options: &Options,
+    annotations: &mut Annotations,
+) {
+    let last = rom_data.banks_count - 1;
+    let reset = cpu_to_label(last, read_word(prg, prg.len() - 4), rom_data);
+    let init = [
+        ("SEI", None),
+        ("CLD", None),
+        ("LDX", Some(0xFF)),
+        ("TXS", None),
+    ];
+    if matches_sequence(reset, &init, prg, cdl, rom_data, options) {
+        log::debug!("Standard init at L{reset:06X}");
+        annotate(annotations, reset, "standard NES init");
+    }
+
+    find_oam_dma(prg, cdl, rom_data, options, annotations);
+    find_rendering_toggles(prg, cdl, rom_data, options, annotations);
+}
+
+/// Comments the writes of an immediate value to `PPUMASK` in the NMI handler,
+/// which often turns the rendering off while it updates the PPU and back on
+/// before returning. Followed from the vector to the first `RTI`.
+fn find_rendering_toggles(
+    prg: &[u8],
+    cdl: &[u8],
+    rom_data: RomData,
+    options: &Options,
+    annotations: &mut Annotations,
+) {
+    let last = rom_data.banks_count - 1;
+    let mut position = cpu_to_label(last, read_word(prg, prg.len() - 6), rom_data);
+    let mut loaded = None;
+    loop {
+        let is_code = prg_offset(position, rom_data).is_some_and(|offset| (cdl[offset] & 1) == 1);
+        let Some(instruction) =
+            decode_one(prg, position, rom_data, DataLabels::default(), options).filter(|_| is_code)
+        else {
+            break;
+        };
+
+        let name = instruction.opcode.name;
+        let addressing = instruction.opcode.addressing;
+        if addressing == Addressing::Absolute
+            && instruction.value == PPUMASK
+            && let Some((register, value)) = loaded
+            && registers::stored_register(instruction.opcode) == Some(register)
+        {
+            // the background and sprites bits
+            let comment = if value & 0x18 == 0 {
+                "rendering off"
+            } else {
+                "rendering on"
+            };
+            log::debug!("NMI {comment} at L{position:06X}");
+            annotate(annotations, position, comment);
+        }
+        if matches!(name, "RTI" | "RTS" | "JMP") {
+            break;
+        }
+
+        loaded = registers::immediate_load(instruction.opcode, instruction.value as u8);
+        position = instruction.next();
+    }
+}
+
+/// Finds the writes of an immediate value to `$4014`, the page of RAM they
+/// copy to the PPU being the sprite buffer.
+fn find_oam_dma(
+    prg: &[u8],
+    cdl: &[u8],
+    rom_data: RomData,
+    options: &Options,
+    annotations: &mut Annotations,
+) {
+    // the previous instruction, if an immediate load
+    let mut loaded = None;
+    let mut offset = 0;
+    while offset < prg.len() {
+        if (cdl[offset] & 1) == 0 {
+            loaded = None;
+            offset += 1;
+            continue;
+        }
+
+        let position = offset_to_label(offset, rom_data);
+        let Some(instruction) = decode_one(prg, position, rom_data, DataLabels::default(), options)
+        else {
+            loaded = None;
+            offset += 1;
+            continue;
+        };
+
+        if instruction.opcode.addressing == Addressing::Absolute
+            && instruction.value == OAM_DMA
+            && let Some((register, page)) = loaded
+            && registers::stored_register(instruction.opcode) == Some(register)
+            // in the RAM, but neither the zero page nor the stack
+            && (0x02..0x08).contains(&page)
+        {
+            log::debug!("OAM DMA from ${page:02X}00 at L{position:06X}");
+            if annotations
+                .oam_page
+                .is_some_and(|oam_page| oam_page != page)
+            {
+                log::warn!("OAM DMA from several pages, only ${page:02X}00 is named");
+            }
+            annotations.oam_page = Some(page);
+            annotate(annotations, position, "OAM DMA of oam_buffer");
+        }
+
+        loaded = registers::immediate_load(instruction.opcode, instruction.value as u8);
+        offset += instruction.size + 1;
+    }
+}
+
+/// Whether the code at `position` is made of these instructions, with these
+/// operands when given.
+fn matches_sequence(
+    mut position: usize,
+    sequence: &[(&str, Option<usize>)],
+    prg: &[u8],
+    cdl: &[u8],
+    rom_data: RomData,
+    options: &Options,
+) -> bool {
+    for (name, value) in sequence {
+        let is_code = prg_offset(position, rom_data).is_some_and(|offset| (cdl[offset] & 1) == 1);
+        let Some(instruction) =
+            decode_one(prg, position, rom_data, DataLabels::default(), options).filter(|_| is_code)
+        else {
+            return false;
+        };
+        if instruction.opcode.name != *name || value.is_some_and(|v| v != instruction.value) {
+            return false;
+        }
+        position = instruction.next();
+    }
+    true
+}
+
+fn annotate(annotations: &mut Annotations, position: usize, comment: &str) {
+    annotations
+        .comments
+        .entry(position)
+        .or_default()
+        .push(comment.to_string());
+}
@@ -0,0 +1,104 @@
+use crate::DataLabels;
+use crate::Options;
+use crate::RomData;
+use crate::decode_one;
+use crate::get_bank_offset;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::Write;
+
+#[derive(Serialize)]
+struct Bank {
+    id: u8,
+    lines: Vec<Line>,
+}
+
+/// An instruction, or a single byte of data.
+#[derive(Serialize)]
+struct Line {
+    /// CPU address
+    address: u16,
+    kind: Kind,
+    bytes: Vec<u8>,
+    /// none for a byte of code whose opcode the CPU doesn't have
+    mnemonic: Option<&'static str>,
+    operand: Option<String>,
+    /// label of the ROM address the instruction jumps to or reads
+    target: Option<String>,
+    label: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    Code,
+    Data,
+    Unknown,
+}
+
+/// Writes the selected PRG banks as JSON, an array of banks each with its
+/// array of lines, the same way they are disassembled.
+pub(crate) fn write_json(
+    prg: &[u8],
+    cdl: &[u8],
+    rom_data: RomData,
+    data_labels: DataLabels,
+    options: &Options,
+    labels: &HashSet<usize>,
+    output: impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut banks = vec![];
+    for id in 0..rom_data.banks_count {
+        if !options.is_bank_selected(id) {
+            continue;
+        }
+
+        let bank_offset = get_bank_offset(id, rom_data);
+        let start = id as usize * rom_data.bank_size;
+        let mut lines = vec![];
+        let mut i = 0;
+        while i < rom_data.bank_size {
+            let position = ((id as usize) << 16) + bank_offset + i;
+            let flags = cdl[start + i];
+            let instruction = decode_one(prg, position, rom_data, data_labels, options)
+                .filter(|_| (flags & 1) == 1)
+                .filter(|instruction| {
+                    // the same as the text output
+                    let operand = &cdl[start + i + 1..start + i + 1 + instruction.size];
+                    !options.operand_data_as_db || !operand.iter().any(|f| (f & 3) == 2)
+                });
+
+            let mut line = Line {
+                address: (position & 0xFFFF) as u16,
+                kind: Kind::Code,
+                bytes: vec![prg[start + i]],
+                mnemonic: None,
+                operand: None,
+                target: None,
+                label: labels
+                    .contains(&position)
+                    .then(|| options.label(position, data_labels)),
+            };
+            match instruction {
+                Some(instruction) => {
+                    line.bytes = prg[start + i..=start + i + instruction.size].to_vec();
+                    line.mnemonic = Some(instruction.opcode.name);
+                    line.operand = Some(instruction.operand.clone()).filter(|op| !op.is_empty());
+                    line.target = instruction
+                        .rom_target()
+                        .map(|target| options.label(target, data_labels));
+                }
+                // an invalid opcode is still code, written as `.db`
+                None if (flags & 1) == 1 && options.cpu.decode(prg[start + i]).is_none() => {}
+                None if (flags & 3) != 0 => line.kind = Kind::Data,
+                None => line.kind = Kind::Unknown,
+            }
+            i += line.bytes.len();
+            lines.push(line);
+        }
+        banks.push(Bank { id, lines });
+    }
+
+    serde_json::to_writer(output, &banks)?;
+    Ok(())
+}
@@ -0,0 +1,3973 @@
+use constants::Constant;
+use flate2::read::GzDecoder;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+mod archive;
+mod cfg;
+mod chr;
+mod compare;
+mod constants;
+mod error;
+mod header;
+mod idioms;
+#[cfg(feature = "serde")]
+mod json;
+mod options;
+mod regions;
+mod registers;
+mod romdb;
+mod symbols;
+mod tags;
+mod trace;
+#[cfg(feature = "tui")]
+mod tui;
+mod unif;
+mod xref;
+
+pub use compare::compare;
+pub use error::DisasmError;
+pub use header::Header;
+pub use header::Mirroring;
+pub use header::TvSystem;
+pub use options::Assembler;
+pub use options::CdlFormat;
+pub use options::Cpu;
+pub use options::HexPrefix;
+pub use options::Layout;
+pub use options::Location;
+pub use options::Options;
+pub use options::Palette;
+pub use options::SectionMode;
+#[cfg(feature = "tui")]
+pub use tui::browse;
+
+/// slots of the memory map: the header (and the trainer when there's one),
+/// the PRG banks at $C000, the CHR windows, the RAM, the swappable PRG banks
+/// at $8000 with `--org-per-section`, the trainer at $7000, the
+/// miscellaneous ROM of NES 2.0, and the battery-backed RAM at $6000 with
+/// `--wram-vars`
+const SLOT_HEADER: u8 = 0;
+const SLOT_PRG: u8 = 1;
+const SLOT_CHR: u8 = 2;
+const SLOT_RAM: u8 = 3;
+const SLOT_SWAPPABLE: u8 = 4;
+const SLOT_TRAINER: u8 = 5;
+const SLOT_MISC: u8 = 6;
+const SLOT_SRAM: u8 = 7;
+/// name of the sprite buffer, with `--annotate-idioms`
+const OAM_BUFFER: &str = "oam_buffer";
+const BANK_SIZE: usize = 0x4000;
+const CHR_SIZE: usize = 0x2000;
+const TRAINER_SIZE: usize = 0x200;
+/// flags of a CDL byte, beside code (bit 0) and data (bit 1)
+const CDL_JUMP_TARGET: u8 = 0x04;
+const CDL_SUBROUTINE: u8 = 0x08;
+const CDL_INDIRECT_DATA: u8 = 0x20;
+
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RomData {
+    pub banks_count: u8,
+    pub mapper: u8,
+    /// size of the PRG banks, 16KB unless `--bank-size` says otherwise
+    pub bank_size: usize,
+    /// CPU address of every bank instead of the one the mapper gives, for a
+    /// bank disassembled alone
+    pub base: Option<usize>,
+}
+
+impl RomData {
+    /// The CPU address of the last bank, fixed at the end of the memory.
+    fn fixed_bank_offset(&self) -> usize {
+        0x10000 - self.bank_size
+    }
+}
+
+/// Labels and comments found before the banks are disassembled.
+#[derive(Default)]
+struct Annotations {
+    labels: HashSet<usize>,
+    comments: HashMap<usize, Vec<String>>,
+    constants: HashSet<Constant>,
+    /// zero-page pointers used by `(zp),Y`
+    pointers: HashSet<u8>,
+    /// page of RAM copied to the PPU by the OAM DMA
+    oam_page: Option<u8>,
+}
+
+/// The final CDL of the PRG, telling the labels on data from the ones on
+/// code for `--data-label-prefix`. Without it, every label is on code.
+#[derive(Copy, Clone, Default)]
+pub(crate) struct DataLabels<'a>(Option<(RomData, &'a [u8])>);
+
+impl<'a> DataLabels<'a> {
+    fn new(rom_data: RomData, cdl: &'a [u8]) -> Self {
+        DataLabels(Some((rom_data, cdl)))
+    }
+
+    /// Whether `label` is on a byte of the PRG that isn't code.
+    pub(crate) fn contains(&self, label: usize) -> bool {
+        self.0.is_some_and(|(rom_data, cdl)| {
+            prg_offset(label, rom_data).is_some_and(|offset| (cdl[offset] & 1) == 0)
+        })
+    }
+}
+
+/// What a disassembly produced.
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    pub prg_banks: u8,
+    pub chr_banks: u8,
+    /// the labels defined in the PRG banks
+    pub labels: usize,
+    pub invalid_opcodes: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "PRG banks: {}", self.prg_banks)?;
+        writeln!(f, "CHR banks: {}", self.chr_banks)?;
+        writeln!(f, "labels: {}", self.labels)?;
+        write!(f, "invalid opcodes: {}", self.invalid_opcodes)
+    }
+}
+
+/// Reads the header of the ROM `filename`, a UNIF one being converted.
+pub fn read_header(filename: &str) -> Result<Header, Box<dyn std::error::Error>> {
+    let rom = fs::read(filename)?;
+    if rom.starts_with(b"UNIF") {
+        return Header::read(&mut &unif::to_ines(&rom)?[..]);
+    }
+    Header::read(&mut &rom[..])
+}
+
+/// Disassembles the ROM `filename` into the directory `output`, or into an
+/// archive if it ends with `.zip` or `.tar`. When several CDL files are
+/// given, a byte's flags are the union of its flags in each.
+pub fn disassemble(
+    filename: &str,
+    cdls: &[String],
+    output: &str,
+    options: &Options,
+) -> Result<Summary, Box<dyn std::error::Error>> {
+    let data = merge_cdls(cdls)?;
+    let rom = fs::read(filename)?;
+
+    if options.dry_run {
+        return disassemble_to_writer(&rom, &data, options, &mut io::sink(), |_| {
+            Ok(Box::new(io::sink()))
+        });
+    }
+
+    if let Some(format) = archive::Format::from_path(output) {
+        let files = archive::Files::default();
+        let mut output_file = files.create("main.s");
+        let sink_files = files.clone();
+        let summary = disassemble_to_writer(&rom, &data, options, &mut output_file, |name| {
+            Ok(Box::new(sink_files.create(name)))
+        })?;
+        if options.manifest {
+            let sizes = files.sizes();
+            write_manifest(&sizes, files.create(MANIFEST))?;
+        }
+        files.write(output, format)?;
+        return Ok(summary);
+    }
+
+    fs::create_dir_all(output)?;
+    let mut output_file = File::create(format!("{output}/main.s"))?;
+
+    let mut names = vec!["main.s".to_string()];
+    let summary = disassemble_to_writer(&rom, &data, options, &mut output_file, |name| {
+        names.push(name.to_string());
+        let path = Path::new(output).join(name);
+        // the banks and the CHR have a directory of their own with `--layout nested`
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(Box::new(File::create(path)?))
+    })?;
+    if options.manifest {
+        let sizes = names
+            .into_iter()
+            .map(|name| {
+                let size = fs::metadata(format!("{output}/{name}"))?.len();
+                Ok((name, size))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        write_manifest(&sizes, File::create(format!("{output}/{MANIFEST}"))?)?;
+    }
+    Ok(summary)
+}
+
+const MANIFEST: &str = "manifest.txt";
+/// file of the miscellaneous ROM of NES 2.0
+const MISC_ROM: &str = "misc.bin";
+
+/// Lists the files written, with their size in bytes, one per line.
+fn write_manifest(files: &[(String, u64)], mut output: impl Write) -> io::Result<()> {
+    for (name, size) in files {
+        writeln!(output, "{size:>8} {name}")?;
+    }
+    Ok(())
+}
+
+fn merge_cdls(filenames: &[String]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut merged: Option<Vec<u8>> = None;
+    for filename in filenames {
+        let data = read_cdl(filename)?;
+        match &mut merged {
+            None => merged = Some(data),
+            Some(merged) => {
+                if merged.len() != data.len() {
+                    return Err(Box::new(DisasmError::CdlLengthMismatch {
+                        expected: merged.len(),
+                        found: data.len(),
+                    }));
+                }
+
+                for (flags, other) in merged.iter_mut().zip(data) {
+                    *flags |= other;
+                }
+            }
+        }
+    }
+
+    Ok(merged.unwrap_or_default())
+}
+
+/// Reads a CDL file, decompressing it if it's gzipped.
+fn read_cdl(filename: &str) -> std::io::Result<Vec<u8>> {
+    let data = fs::read(filename)?;
+    if !data.starts_with(&[0x1F, 0x8B]) && !filename.ends_with(".gz") {
+        return Ok(data);
+    }
+
+    let mut decompressed = vec![];
+    GzDecoder::new(&data[..]).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// Disassembles `rom` into `main`, the other generated files (`bankNNN.asm`,
+/// `bankNNN.chr`) being written to the writers returned by `sink`, which
+/// receives the name of the file.
+pub fn disassemble_to_writer(
+    rom: &[u8],
+    data: &[u8],
+    options: &Options,
+    output_file: &mut impl Write,
+    sink: impl FnMut(&str) -> std::io::Result<Box<dyn Write>>,
+) -> Result<Summary, Box<dyn std::error::Error>> {
+    disassemble_with_hook(rom, data, options, output_file, sink, &mut |_, _| {})
+}
+
+/// Same as `disassemble_to_writer`, but `hook` is called with every PRG bank
+/// once it is disassembled and before it is written, with its number. The
+/// hook can change the lines, add some with `Line::new`, or add and remove
+/// labels: a line gets a label when its position is in `labels`. The labels
+/// referenced by another bank are not updated.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nes_disasm::Line;
+/// use nes_disasm::Options;
+/// use std::io;
+///
+/// let rom = std::fs::read("game.nes")?;
+/// let cdl = std::fs::read("game.cdl")?;
+/// nes_disasm::disassemble_with_hook(
+///     &rom,
+///     &cdl,
+///     &Options::new(),
+///     &mut io::stdout(),
+///     |_| Ok(Box::new(io::sink())),
+///     &mut |id, bank| bank.lines.insert(0, Line::new(None, format!("; bank {id}"))),
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn disassemble_with_hook(
+    rom: &[u8],
+    data: &[u8],
+    options: &Options,
+    output_file: &mut impl Write,
+    mut sink: impl FnMut(&str) -> std::io::Result<Box<dyn Write>>,
+    hook: &mut dyn FnMut(u8, &mut BankResult),
+) -> Result<Summary, Box<dyn std::error::Error>> {
+    // a UNIF image is converted, the rest of the disassembly only knows iNES
+    let converted;
+    let mut rom = if rom.starts_with(b"UNIF") {
+        log::info!("Converting the UNIF ROM to iNES");
+        converted = unif::to_ines(rom)?;
+        &converted[..]
+    } else {
+        rom
+    };
+
+    let header = Header::read(&mut rom)?;
+
+    // a truncated file only gets the banks it really contains
+    let mut available = rom.len();
+    if header.trainer {
+        available = available.saturating_sub(TRAINER_SIZE);
+    }
+    // NES 2.0 counts past 255 banks with the most significant bits of byte 9
+    let (declared_prg, declared_chr) = (
+        header.prg_rom_size() / BANK_SIZE,
+        header.chr_rom_size() / CHR_SIZE,
+    );
+    let prg_banks_count = declared_prg.min(available / BANK_SIZE);
+    available -= prg_banks_count * BANK_SIZE;
+    let chr_banks_count = declared_chr.min(available / CHR_SIZE);
+    if prg_banks_count != declared_prg || chr_banks_count != declared_chr {
+        log::warn!(
+            "The header declares {declared_prg} PRG and {declared_chr} CHR banks but the file only contains {prg_banks_count} and {chr_banks_count}.",
+        );
+    }
+    if chr_banks_count > u8::MAX as usize {
+        log::warn!("Only the first {} CHR banks are read.", u8::MAX);
+    }
+    let chr_banks_count = chr_banks_count.min(u8::MAX as usize) as u8;
+    if prg_banks_count == 0 {
+        return Err(DisasmError::NoPrgBanks.into());
+    }
+    // NES 2.0 stores the miscellaneous ROMs after the CHR, nothing else
+    // should be there
+    let trailing = available - chr_banks_count as usize * CHR_SIZE;
+    let misc_size = if header.misc_roms > 0 { trailing } else { 0 };
+    if trailing > 0 && misc_size == 0 && chr_banks_count as usize == declared_chr {
+        log::warn!("The {trailing} bytes after the CHR banks are ignored.");
+    }
+    // the iNES header counts the PRG in 16KB units, whatever the banks are
+    let prg_size = prg_banks_count * BANK_SIZE;
+    let bank_size = options.bank_size.unwrap_or(BANK_SIZE);
+    if ![0x2000, BANK_SIZE, 0x8000].contains(&bank_size)
+        || !prg_size.is_multiple_of(bank_size)
+        || prg_size / bank_size > u8::MAX as usize
+    {
+        return Err(Box::new(DisasmError::BankSize {
+            bank_size,
+            prg_size,
+        }));
+    }
+    let rom_data = RomData {
+        banks_count: (prg_size / bank_size) as u8,
+        mapper: header.mapper,
+        bank_size,
+        base: None,
+    };
+
+    if !is_supported_mapper(header.mapper) {
+        if options.strict {
+            return Err(Box::new(DisasmError::UnsupportedMapper(header.mapper)));
+        }
+
+        log::warn!(
+            "Unsupported mapper {}, the last PRG bank is guessed to be fixed at the end of the memory, the labels might be wrong.",
+            header.mapper
+        );
+        writeln!(
+            output_file,
+            "; WARNING: mapper {} is not supported, the last PRG bank is guessed to be fixed at the end of the memory and the others at $8000.\n",
+            header.mapper
+        )?;
+    }
+
+    match &options.memory_map {
+        // asm6 writes the ROM in order
+        _ if !options.assembler.has_sections() => {}
+        Some(path) => {
+            log::info!("Using the memory map {path}");
+            writeln!(output_file, "{}", fs::read_to_string(path)?.trim_end())?;
+            writeln!(output_file)?;
+        }
+        None => write_memory_map(
+            &header,
+            rom_data,
+            chr_banks_count,
+            misc_size,
+            options,
+            output_file,
+        )?,
+    }
+
+    if options.assembler.has_sections() {
+        write_bank_header(output_file, WlaBank::Header, rom_data, options)?;
+        writeln!(output_file)?;
+        writeln!(
+            output_file,
+            "{}.SECTION \"{}\" {}\n",
+            options.indentation(),
+            options.section_name("Header"),
+            options.section_mode
+        )?;
+    }
+    if let Some(path) = &options.rom_db {
+        let roms = romdb::parse_rom_db(&fs::read_to_string(path)?)?;
+        let start = if header.trainer { TRAINER_SIZE } else { 0 };
+        let crc = crc32fast::hash(&rom[start..start + prg_size]);
+        if let Some(known) = roms.get(&crc) {
+            log::info!("The PRG (CRC32 {crc:08X}) is the one of {}", known.title);
+            writeln!(output_file, "; game: {} (PRG CRC32 {crc:08X})", known.title)?;
+            if let Some(hint) = &known.hint {
+                writeln!(output_file, "; hint: {hint}")?;
+            }
+        }
+    }
+    writeln!(output_file, "; TV system: {}", header.tv_system)?;
+    let indent = options.indentation();
+    // written from the bytes read so that it reassembles identically
+    let bytes = header.to_bytes();
+    writeln!(
+        output_file,
+        "{indent}.db \"NES\", {}",
+        options.hex(bytes[3] as usize, 2)
+    )?;
+    writeln!(
+        output_file,
+        "{indent}.db {}",
+        options.hex(bytes[4] as usize, 2)
+    )?;
+    writeln!(
+        output_file,
+        "{indent}.db {}",
+        options.hex(bytes[5] as usize, 2)
+    )?;
+    let flags: Vec<String> = bytes[6..]
+        .iter()
+        .map(|b| options.hex(*b as usize, 2))
+        .collect();
+    // asm6 wants the bytes separated by commas
+    let separator = if options.assembler.has_sections() {
+        " "
+    } else {
+        ", "
+    };
+    writeln!(output_file, "{indent}.db {}", flags.join(separator))?;
+    if header.trainer {
+        let mut trainer = vec![0u8; TRAINER_SIZE];
+        rom.read_exact(&mut trainer)?;
+        writeln!(output_file, "\n; trainer, loaded at $7000")?;
+        writeln!(
+            output_file,
+            "{indent}{}",
+            options.assembler.incbin("trainer.bin")
+        )?;
+        sink("trainer.bin")?.write_all(&trainer)?;
+    }
+    if options.assembler.has_sections() {
+        writeln!(output_file, "\n{indent}.ENDS\n")?;
+
+        writeln!(
+            output_file,
+            "{indent}.RAMSECTION \"{}\" SLOT {SLOT_RAM}",
+            options.section_name("RAM")
+        )?;
+        writeln!(output_file, "{indent}.ENDS\n")?;
+    } else {
+        writeln!(output_file)?;
+    }
+
+    let mut prg = vec![0u8; prg_size];
+    rom.read_exact(&mut prg)?;
+    let expected = prg.len() + chr_banks_count as usize * CHR_SIZE;
+    if data.len() != expected {
+        log::warn!(
+            "The CDL is {} bytes long but the ROM needs {expected}.",
+            data.len()
+        );
+    }
+    // a CDL covering only the first banks leaves the others unknown
+    let mut cdl = vec![0u8; prg.len()];
+    let logged = data.len().min(prg.len());
+    cdl[..logged].copy_from_slice(&data[..logged]);
+    if logged < prg.len() {
+        let (first, last) = (logged / bank_size, rom_data.banks_count as usize - 1);
+        if first == last {
+            log::warn!("The CDL doesn't cover the PRG bank {first}, left unknown.");
+        } else {
+            log::warn!("The CDL doesn't cover the PRG banks {first} to {last}, left unknown.");
+        }
+    }
+
+    let mut annotations = Annotations::default();
+    for (offset, flags) in cdl.iter_mut().enumerate() {
+        // the bytes read indirectly are data, even inside of logged code
+        if *flags & CDL_INDIRECT_DATA != 0 {
+            *flags = (*flags & !1) | 2;
+        }
+        if options.cdl_format == CdlFormat::Mesen
+            && *flags & (CDL_JUMP_TARGET | CDL_SUBROUTINE) != 0
+        {
+            annotations.labels.insert(offset_to_label(offset, rom_data));
+        }
+    }
+    if let Some(path) = &options.regions {
+        let regions = regions::parse_regions(&fs::read_to_string(path)?)?;
+        regions::apply_regions(&regions, &mut cdl, rom_data)?;
+    }
+    let entries = options
+        .entries
+        .iter()
+        .map(|location| resolve_location(*location, rom_data))
+        .collect::<Result<Vec<_>, _>>()?;
+    if options.trace || !entries.is_empty() {
+        log::info!("Tracing the code from the interrupt vectors and the entry points");
+        trace::trace(
+            &prg,
+            &mut cdl,
+            rom_data,
+            options,
+            &entries,
+            &mut annotations,
+        );
+    }
+    if options.follow_calls {
+        log::info!("Following the calls of the logged code");
+        trace::follow_calls(&prg, &mut cdl, rom_data, options, &mut annotations);
+    }
+    if options.annotate_idioms {
+        idioms::annotate_idioms(&prg, &cdl, rom_data, options, &mut annotations);
+    }
+    if options.data_xref {
+        xref::annotate_data_reads(&prg, &cdl, rom_data, options, &mut annotations);
+    }
+    // the names of the labels depend on the final CDL
+    let data_labels = DataLabels::new(rom_data, &cdl);
+
+    if let Some(threshold) = options.define_constants {
+        let constants = constants::find_constants(&prg, &cdl, rom_data, options, threshold);
+        for constant in &constants {
+            writeln!(
+                output_file,
+                "{indent}{}",
+                options
+                    .assembler
+                    .define(&constant.name(), &options.hex(constant.value() as usize, 2))
+            )?;
+        }
+        if !constants.is_empty() {
+            writeln!(output_file)?;
+        }
+        annotations.constants.extend(constants);
+    }
+
+    if options.zp_pointers {
+        let pointers = constants::find_zero_page_pointers(&prg, &cdl, rom_data, options);
+        for pointer in &pointers {
+            let addr = options.hex(*pointer as usize, 2);
+            writeln!(
+                output_file,
+                "{indent}{}",
+                options.assembler.enum_start(&addr)
+            )?;
+            let name = constants::pointer_name(*pointer);
+            writeln!(output_file, "{}", options.variable(&name, 2))?;
+            writeln!(output_file, "{indent}{}", options.assembler.enum_end())?;
+        }
+        if !pointers.is_empty() {
+            writeln!(output_file)?;
+        }
+        annotations.pointers.extend(pointers);
+    }
+
+    if let Some(page) = annotations.oam_page {
+        let addr = options.hex((page as usize) << 8, 4);
+        writeln!(
+            output_file,
+            "{indent}{}",
+            options.assembler.enum_start(&addr)
+        )?;
+        writeln!(output_file, "{}", options.variable(OAM_BUFFER, 256))?;
+        writeln!(output_file, "{indent}{}\n", options.assembler.enum_end())?;
+    }
+
+    if let Some(location) = options.cfg {
+        let root = resolve_location(location, rom_data)?;
+        let mut output = sink(&format!("cfg_L{root:06X}.dot"))?;
+        cfg::write_cfg(
+            root,
+            &prg,
+            &cdl,
+            rom_data,
+            data_labels,
+            options,
+            &mut output,
+        )?;
+    }
+
+    let mut summary = Summary {
+        prg_banks: rom_data.banks_count,
+        chr_banks: chr_banks_count,
+        ..Default::default()
+    };
+    let mut labels = BTreeSet::new();
+    let mut defined = HashSet::new();
+    let mut flat_banks = vec![];
+    if options.flat {
+        let mut offsets = HashMap::new();
+        for id in (0..rom_data.banks_count).filter(|id| options.is_bank_selected(*id)) {
+            let bank_offset = get_bank_offset(id, rom_data);
+            if let Some(other) = offsets.insert(bank_offset, id) {
+                return Err(Box::new(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "--flat needs the banks at different addresses, but banks {other} and {id} are both at {}; select one with --start-bank and --end-bank",
+                        options.hex(bank_offset, 4)
+                    ),
+                )));
+            }
+        }
+    }
+    if !options.bank_order.is_empty() {
+        let mut order = options.bank_order.clone();
+        order.sort_unstable();
+        if !order.iter().copied().eq(0..rom_data.banks_count) {
+            return Err(Box::new(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "--bank-order must list each of the {} PRG banks once",
+                    rom_data.banks_count
+                ),
+            )));
+        }
+    }
+    for id in 0..rom_data.banks_count {
+        let name = format!("bank{:03}.asm", options.logical_bank(id));
+        let include = options.assembler.include(&options.bank_path(&name));
+        if !options.is_bank_selected(id) {
+            writeln!(output_file, "; {include}")?;
+            continue;
+        }
+
+        log::info!("Disassembling PRG bank {id}");
+        writeln!(output_file, "{indent}{include}")?;
+
+        let bank_offset = (id as usize) * bank_size;
+        let bank = &prg[bank_offset..bank_offset + bank_size];
+        let cld_part = &cdl[bank_offset..bank_offset + bank_size];
+
+        // nothing logged: a wall of unknown bytes, unless every byte is
+        // taken as code
+        let unlogged = cld_part.iter().all(|flags| (flags & 3) == 0);
+        let mut result = if unlogged && options.linear_unlogged_banks {
+            let linear = vec![1; bank_size];
+            disassemble_prg_bank(
+                id,
+                bank,
+                rom_data,
+                &linear,
+                data_labels,
+                options,
+                &annotations,
+            )?
+        } else {
+            disassemble_prg_bank(
+                id,
+                bank,
+                rom_data,
+                cld_part,
+                data_labels,
+                options,
+                &annotations,
+            )?
+        };
+        if unlogged {
+            log::warn!("The CDL logged nothing in PRG bank {id}.");
+            let comment = if options.linear_unlogged_banks {
+                "; the CDL logged nothing in this bank, disassembled as code"
+            } else {
+                "; WARNING: the CDL logged nothing in this bank, try --trace or --linear-unlogged-banks"
+            };
+            result.lines.insert(0, Line::new(None, comment));
+        }
+        hook(id, &mut result);
+        write_prg_bank(
+            id,
+            &mut result,
+            rom_data,
+            cld_part,
+            data_labels,
+            options,
+            &mut sink,
+        )?;
+        summary.labels += result.defined.len();
+        summary.invalid_opcodes += result.invalid_opcodes;
+        labels.extend(result.labels.iter());
+        defined.extend(result.defined.iter());
+        if options.flat {
+            let bank_offset = get_bank_offset(id, rom_data);
+            flat_banks.push((bank_offset, id, result));
+        }
+    }
+
+    if options.flat {
+        log::info!("Writing flat.asm");
+        write_flat(
+            &mut flat_banks,
+            rom_data,
+            data_labels,
+            options,
+            &mut sink("flat.asm")?,
+        )?;
+    }
+
+    if options.verify {
+        verify_labels(&labels, &defined, data_labels, options);
+    }
+
+    if let Some(name) = &options.ghidra {
+        log::info!("Writing the Ghidra script {name}");
+        symbols::write_ghidra_script(&labels, rom_data, data_labels, options, &mut sink(name)?)?;
+    }
+
+    if let Some(name) = &options.tags {
+        log::info!("Writing the instruction tags {name}");
+        tags::write_tags(&prg, &cdl, rom_data, options, &mut sink(name)?)?;
+    }
+
+    if options.labels_csv {
+        log::info!("Writing labels.csv");
+        symbols::write_csv(
+            &labels,
+            rom_data,
+            data_labels,
+            header.trainer,
+            options,
+            &mut sink("labels.csv")?,
+        )?;
+    }
+
+    #[cfg(feature = "serde")]
+    if let Some(name) = &options.json {
+        log::info!("Writing {name}");
+        json::write_json(
+            &prg,
+            &cdl,
+            rom_data,
+            data_labels,
+            options,
+            &defined,
+            sink(name)?,
+        )?;
+    }
+
+    let wram: BTreeSet<usize> = labels.into_iter().filter(|addr| is_wram(*addr)).collect();
+
+    if !header.prg_ram && !wram.is_empty() {
+        log::warn!("The header says there's no PRG-RAM, but $6000-$7FFF is used.");
+    }
+    if has_sram_section(&header, options) && !wram.is_empty() {
+        writeln!(output_file, "\n; save RAM variables, kept by the battery")?;
+        writeln!(
+            output_file,
+            "{indent}.RAMSECTION \"{}\" SLOT {SLOT_SRAM}",
+            options.section_name("SRAM")
+        )?;
+        // placed from $6000, the bytes in between reserved
+        let mut next = 0x6000;
+        for addr in wram {
+            if addr > next {
+                writeln!(
+                    output_file,
+                    "{indent}{indent}{} dsb {}",
+                    wram_label(next),
+                    addr - next
+                )?;
+            }
+            let variable = options.variable(&wram_label(addr), 1);
+            if options.label_comments {
+                writeln!(output_file, "{variable} ; {}", options.hex(addr, 4))?;
+            } else {
+                writeln!(output_file, "{variable}")?;
+            }
+            next = addr + 1;
+        }
+        writeln!(output_file, "{indent}.ENDS")?;
+    } else if options.wram_vars && header.prg_ram && !wram.is_empty() {
+        writeln!(output_file, "\n; save RAM variables")?;
+        let mut previous = None;
+        for addr in wram {
+            if previous != Some(addr - 1) {
+                if previous.is_some() {
+                    writeln!(output_file, "{indent}{}", options.assembler.enum_end())?;
+                }
+                let start = options.hex(addr, 4);
+                writeln!(
+                    output_file,
+                    "{indent}{}",
+                    options.assembler.enum_start(&start)
+                )?;
+            }
+            let variable = options.variable(&wram_label(addr), 1);
+            if options.label_comments {
+                writeln!(output_file, "{variable} ; {}", options.hex(addr, 4))?;
+            } else {
+                writeln!(output_file, "{variable}")?;
+            }
+            previous = Some(addr);
+        }
+        writeln!(output_file, "{indent}{}", options.assembler.enum_end())?;
+    }
+
+    // the first bank with a given content
+    let mut chr_banks: HashMap<Vec<u8>, u8> = HashMap::new();
+    // every bank, for the combined sheet
+    let mut all_chr_banks = vec![];
+    let mut chr_description = if options.prg_only {
+        Some(sink("chr.txt")?)
+    } else {
+        None
+    };
+    if let Some(output) = &mut chr_description {
+        if chr_banks_count == 0 {
+            writeln!(output, "no CHR ROM, the cartridge has CHR RAM")?;
+        } else {
+            writeln!(
+                output,
+                "CHR banks: {chr_banks_count} of {CHR_SIZE} bytes ({} bytes)",
+                chr_banks_count as usize * CHR_SIZE
+            )?;
+        }
+    }
+    let chr_start = 0x10 + if header.trainer { TRAINER_SIZE } else { 0 } + prg_size;
+    for id in 0..chr_banks_count {
+        let mut bank = vec![0u8; CHR_SIZE];
+        rom.read_exact(&mut bank)?;
+        if let Some(output) = &mut chr_description {
+            let offset = chr_start + id as usize * CHR_SIZE;
+            writeln!(
+                output,
+                "bank {id}: at {} in the ROM",
+                options.hex(offset, 6)
+            )?;
+        }
+        if options.skips_chr() {
+            continue;
+        }
+        if options.chr_sheet.is_some() {
+            all_chr_banks.push(bank.clone());
+        }
+
+        let first = chr_banks.get(&bank).filter(|_| !options.no_dedupe).copied();
+        let file = first.unwrap_or(id);
+        let window = chr_window_size(header.mapper);
+        // asm6 has no banks to split the CHR into
+        let windows = if options.assembler.has_sections() {
+            CHR_SIZE / window
+        } else {
+            1
+        };
+        for k in 0..windows {
+            writeln!(output_file)?;
+            let bank = WlaBank::Chr(id as usize * windows + k);
+            write_bank_header(output_file, bank, rom_data, options)?;
+            if let Some(first) = first.filter(|_| k == 0) {
+                log::info!("CHR bank {id} is the same as CHR bank {first}");
+                writeln!(output_file, "; same as CHR bank {first}")?;
+            }
+            let name = options.chr_path(&format!("bank{file:03}.chr"));
+            if windows == 1 {
+                writeln!(output_file, "{indent}{}", options.assembler.incbin(&name))?;
+            } else {
+                writeln!(
+                    output_file,
+                    "{indent}.INCBIN \"{name}\" SKIP {} READ {}",
+                    options.hex(k * window, 4),
+                    options.hex(window, 4)
+                )?;
+            }
+        }
+        if first.is_some() {
+            continue;
+        }
+
+        log::info!("Extracting CHR bank {id}");
+        sink(&options.chr_path(&format!("bank{id:03}.chr")))?.write_all(&bank)?;
+        if options.chr_png {
+            let name = options.chr_path(&format!("bank{id:03}.png"));
+            chr::Sheet::from_bank(&bank).write_png(&options.palette, sink(&name)?)?;
+        }
+        chr_banks.insert(bank, id);
+    }
+
+    if let Some(name) = options
+        .chr_sheet
+        .as_ref()
+        .filter(|_| !all_chr_banks.is_empty())
+    {
+        log::info!("Writing the CHR sheet {name}");
+        chr::Sheet::from_banks(&all_chr_banks, options.tiles_per_row)
+            .write_png(&options.palette, sink(name)?)?;
+    }
+
+    if misc_size > 0 {
+        log::info!("Extracting the {misc_size} bytes of miscellaneous ROM");
+        let mut misc = vec![0u8; misc_size];
+        rom.read_exact(&mut misc)?;
+        sink(MISC_ROM)?.write_all(&misc)?;
+
+        writeln!(output_file)?;
+        let windows = written_chr_windows(chr_banks_count, header.mapper, options);
+        write_bank_header(output_file, WlaBank::Misc(windows), rom_data, options)?;
+        if options.assembler.has_sections() {
+            writeln!(
+                output_file,
+                "{indent}.SECTION \"{}\" {}",
+                options.section_name("Misc"),
+                options.section_mode
+            )?;
+        }
+        writeln!(
+            output_file,
+            "{indent}{}",
+            options.assembler.incbin(MISC_ROM)
+        )?;
+        if options.assembler.has_sections() {
+            writeln!(output_file, "{indent}.ENDS")?;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Writes the `.MEMORYMAP` and the `.ROMBANKMAP` matching the ROM.
+fn write_memory_map(
+    header: &Header,
+    rom_data: RomData,
+    chr_banks_count: u8,
+    misc_size: usize,
+    options: &Options,
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    let indent = options.indentation();
+    let inner = indent.repeat(2);
+    // the trainer is stored right after the header
+    let header_size = if header.trainer {
+        0x10 + TRAINER_SIZE
+    } else {
+        0x10
+    };
+
+    writeln!(output, "{indent}.MEMORYMAP")?;
+    writeln!(output, "{inner}DEFAULTSLOT {SLOT_PRG}")?;
+    writeln!(output, "{inner}SLOTSIZE {}", options.hex(header_size, 4))?;
+    writeln!(output, "{inner}SLOT {SLOT_HEADER} {}", options.hex(0, 4))?;
+    writeln!(
+        output,
+        "{inner}SLOTSIZE {}",
+        options.hex(rom_data.bank_size, 0)
+    )?;
+    writeln!(
+        output,
+        "{inner}SLOT {SLOT_PRG} {}",
+        options.hex(rom_data.fixed_bank_offset(), 4)
+    )?;
+    if !options.skips_chr() {
+        writeln!(
+            output,
+            "{inner}SLOTSIZE {}",
+            options.hex(chr_window_size(header.mapper), 0)
+        )?;
+        writeln!(output, "{inner}SLOT {SLOT_CHR} {}", options.hex(0, 4))?;
+    }
+    writeln!(output, "{inner}SLOTSIZE {}", options.hex(0x800, 0))?;
+    writeln!(output, "{inner}SLOT {SLOT_RAM} {}", options.hex(0, 4))?;
+    if options.org_per_section {
+        // the swappable banks, at their CPU address
+        writeln!(
+            output,
+            "{inner}SLOTSIZE {}",
+            options.hex(rom_data.bank_size, 0)
+        )?;
+        writeln!(
+            output,
+            "{inner}SLOT {SLOT_SWAPPABLE} {}",
+            options.hex(0x8000, 4)
+        )?;
+    }
+    if header.trainer {
+        writeln!(
+            output,
+            "{inner}; the trainer, copied to $7000 before the game starts"
+        )?;
+        writeln!(output, "{inner}SLOTSIZE {}", options.hex(TRAINER_SIZE, 0))?;
+        writeln!(
+            output,
+            "{inner}SLOT {SLOT_TRAINER} {}",
+            options.hex(0x7000, 4)
+        )?;
+    }
+    if misc_size > 0 {
+        writeln!(output, "{inner}SLOTSIZE {}", options.hex(misc_size, 4))?;
+        writeln!(output, "{inner}SLOT {SLOT_MISC} {}", options.hex(0, 4))?;
+    }
+    if has_sram_section(header, options) {
+        writeln!(output, "{inner}SLOTSIZE {}", options.hex(0x2000, 0))?;
+        writeln!(output, "{inner}SLOT {SLOT_SRAM} {}", options.hex(0x6000, 4))?;
+    }
+    if header.mirroring == Mirroring::FourScreen {
+        writeln!(
+            output,
+            "{inner}; four-screen mirroring: the cartridge adds 2KB of nametable RAM to the PPU"
+        )?;
+    }
+    writeln!(output, "{indent}.ENDME\n")?;
+
+    let window = chr_window_size(header.mapper);
+    let written_chr_banks_count = written_chr_windows(chr_banks_count, header.mapper, options);
+    let misc_banks_count = usize::from(misc_size > 0);
+
+    writeln!(output, "{indent}.ROMBANKMAP")?;
+    writeln!(
+        output,
+        "{inner}BANKSTOTAL {}",
+        chr_bank_number(written_chr_banks_count, rom_data.banks_count) + misc_banks_count
+    )?;
+    writeln!(output, "{inner}BANKSIZE {}", options.hex(header_size, 4))?;
+    writeln!(output, "{inner}BANKS 1")?;
+    writeln!(
+        output,
+        "{inner}BANKSIZE {}",
+        options.hex(rom_data.bank_size, 0)
+    )?;
+    writeln!(output, "{inner}BANKS {}", rom_data.banks_count)?;
+    if written_chr_banks_count > 0 {
+        writeln!(output, "{inner}BANKSIZE {}", options.hex(window, 0))?;
+        writeln!(output, "{inner}BANKS {written_chr_banks_count}")?;
+    }
+    if misc_banks_count > 0 {
+        writeln!(output, "{inner}BANKSIZE {}", options.hex(misc_size, 4))?;
+        writeln!(output, "{inner}BANKS {misc_banks_count}")?;
+    }
+    writeln!(output, "{indent}.ENDRO\n")?;
+
+    Ok(())
+}
+
+/// A disassembled PRG bank, given to the hook of `disassemble_with_hook`
+/// before it is written. The labels are `bank << 16 | address`, the RAM
+/// ones being the address alone.
+#[derive(Clone, Debug, Default)]
+pub struct BankResult {
+    /// the labels referenced, including the ones of other banks and the RAM;
+    /// a line whose position is one of them gets the label
+    pub labels: HashSet<usize>,
+    /// the labels written in the bank, empty until it is written
+    pub defined: HashSet<usize>,
+    pub invalid_opcodes: usize,
+    pub lines: Vec<Line>,
+    /// the files of unknown bytes included by the lines, with `--incbin-threshold`
+    pub blobs: Vec<(String, Vec<u8>)>,
+}
+
+/// A line of a disassembled bank.
+#[derive(Clone, Debug, Default)]
+pub struct Line {
+    /// the label of the instruction or data, `None` for the comments and the
+    /// blank lines
+    pub position: Option<usize>,
+    /// the text written, e.g. `    LDA L01C100.w,X` or `    .db $00`
+    pub text: String,
+    /// first line of a routine, with `split_routines`
+    pub(crate) routine_start: bool,
+    /// the label in ROM the instruction jumps to or reads
+    pub target: Option<usize>,
+}
+
+impl Line {
+    pub fn new(position: Option<usize>, text: impl Into<String>) -> Self {
+        Line {
+            position,
+            text: text.into(),
+            routine_start: false,
+            target: None,
+        }
+    }
+}
+
+/// Disassembles a bank alone, placed at `base` in the CPU memory, without
+/// the header nor the other banks: the references outside of it are left as
+/// addresses. The result isn't written, so its `defined` labels are empty,
+/// and a `cdl` shorter than the bank leaves the rest unknown.
+///
+/// ```
+/// use nes_disasm::Options;
+///
+/// // LDA #$00 / RTS
+/// let bank = [0xA9, 0x00, 0x60];
+/// let result = nes_disasm::disassemble_bank(&bank, &[1, 1, 1], 0xC000, &Options::new()).unwrap();
+/// assert_eq!(result.lines[0].text, "    LDA #0");
+/// ```
+pub fn disassemble_bank(
+    bank: &[u8],
+    cdl: &[u8],
+    base: u16,
+    options: &Options,
+) -> Result<BankResult, Box<dyn std::error::Error>> {
+    let base = base as usize;
+    if bank.is_empty() || base < 0x8000 || base + bank.len() > 0x10000 {
+        return Err(Box::new(Error::new(
+            ErrorKind::InvalidInput,
+            "The bank must be inside $8000-$FFFF.",
+        )));
+    }
+
+    let rom_data = RomData {
+        banks_count: 1,
+        mapper: 0,
+        bank_size: bank.len(),
+        base: Some(base),
+    };
+    let mut flags = vec![0u8; bank.len()];
+    let logged = cdl.len().min(bank.len());
+    flags[..logged].copy_from_slice(&cdl[..logged]);
+    let data_labels = DataLabels::new(rom_data, &flags);
+    disassemble_prg_bank(
+        0,
+        bank,
+        rom_data,
+        &flags,
+        data_labels,
+        options,
+        &Annotations::default(),
+    )
+}
+
+fn disassemble_prg_bank(
+    id: u8,
+    bank: &[u8],
+    rom_data: RomData,
+    cdl: &[u8],
+    data_labels: DataLabels,
+    options: &Options,
+    annotations: &Annotations,
+) -> Result<BankResult, Box<dyn std::error::Error>> {
+    let mut buffer = vec![];
+    // indexes in `buffer` where a routine starts, with --split-routines
+    let mut routine_starts = BTreeSet::from([0]);
+    let mut subroutines = HashSet::new();
+    // the ROM labels referenced by the instructions, by position
+    let mut targets = HashMap::new();
+    // the register and the value of the last immediate load, with the
+    // position of the next instruction
+    let mut last_immediate = None;
+    let mut invalid_opcodes = 0;
+
+    let mut i = 0;
+    let mut print_label = true;
+    let mut labels = annotations.labels.clone();
+    let mut is_inside_data = false;
+    let indent = options.indentation();
+    // where the words of the pointer tables are counted from: the start of
+    // the data, or after the last single byte with `word_align`
+    let mut word_start = 0;
+    let mut pointers = 0;
+    // the lines of single data or unknown bytes in a row: their index in
+    // `buffer`, the offset of the first one, their count and if they're data
+    let mut byte_runs: Vec<(usize, usize, usize, bool)> = vec![];
+
+    let bank_offset = get_bank_offset(id, rom_data);
+    // the interrupt vectors, when the bank ends at $FFFF and they aren't code
+    let vectors = bank.len().checked_sub(6).filter(|vectors| {
+        bank_offset + bank.len() == 0x10000 && cdl[*vectors..].iter().all(|f| (f & 1) == 0)
+    });
+    while i < bank.len() {
+        let g_offset = i + id as usize * 0x10000 + bank_offset;
+
+        if let Some(comments) = annotations.comments.get(&g_offset) {
+            for comment in comments {
+                buffer.push((0, format!("; {comment}")));
+            }
+        }
+
+        if vectors == Some(i) {
+            if is_inside_data {
+                buffer.push((0, "; end of data".to_string()));
+                is_inside_data = false;
+            }
+            buffer.push((0, "; interrupt vectors".to_string()));
+            for (k, name) in ["NMI", "RESET", "IRQ"].into_iter().enumerate() {
+                let addr = (bank[i + 2 * k + 1] as usize) << 8 | bank[i + 2 * k] as usize;
+                let (operand, target) = word_operand(id, addr, rom_data, data_labels, options);
+                labels.extend(target);
+                buffer.push((g_offset + 2 * k, format!("{indent}.dw {operand} ; {name}")));
+            }
+            break;
+        }
+
+        if (cdl[i] & 1) == 1 {
+            // is code
+            if is_inside_data {
+                buffer.push((0, "; end of data".to_string()));
+                is_inside_data = false;
+            }
+
+            // self-modifying code, or a byte that's both an opcode and data
+            if (cdl[i] & 3) == 3 && !options.no_code_data_comments {
+                buffer.push((0, "; code+data".to_string()));
+            }
+
+            let op = bank[i] as usize;
+            if let Some(opcode) = options.cpu.decode(bank[i]) {
+                if print_label {
+                    labels.insert(g_offset);
+                    print_label = false;
+                }
+
+                let operands = [
+                    bank.get(i + 1).copied().unwrap_or(0),
+                    bank.get(i + 2).copied().unwrap_or(0),
+                ];
+                let (size, mut output, target) = write_addressing(
+                    &opcode.addressing,
+                    &operands,
+                    id,
+                    g_offset,
+                    rom_data,
+                    data_labels,
+                    options,
+                )?;
+                if i + size >= bank.len() {
+                    buffer.push((
+                        g_offset,
+                        format!(
+                            "{indent}.db {} ; operand past the end of the bank",
+                            options.hex(op, 2)
+                        ),
+                    ));
+                    i += 1;
+                    continue;
+                }
+
+                if matches!(
+                    opcode.addressing,
+                    Addressing::Absolute | Addressing::AbsoluteX | Addressing::AbsoluteY
+                ) {
+                    let addr = (operands[1] as usize) << 8 | operands[0] as usize;
+                    if is_in_unknown_bank(id, addr, rom_data) {
+                        buffer.push((
+                            0,
+                            format!(
+                                "; {} is in the swappable bank, which one is unknown",
+                                options.hex(addr, 4)
+                            ),
+                        ));
+                    }
+                    // the CPU sees the 2KB of RAM up to $1FFF, the CHR
+                    // is only reachable through the PPU registers
+                    if (0x0800..0x2000).contains(&addr) {
+                        buffer.push((
+                            0,
+                            format!(
+                                "; {} is a mirror of the RAM at {}",
+                                options.hex(addr, 4),
+                                options.hex(addr & 0x07FF, 4)
+                            ),
+                        ));
+                    }
+                }
+
+                let operand_is_data = cdl
+                    .iter()
+                    .skip(i + 1)
+                    .take(size)
+                    .any(|flags| (flags & 3) == 2);
+                if operand_is_data && options.operand_data_as_db {
+                    buffer.push((
+                        g_offset,
+                        format!(
+                            "{indent}.db {} ; operand flagged as data",
+                            options.hex(op, 2)
+                        ),
+                    ));
+                    i += 1;
+                    continue;
+                } else if operand_is_data {
+                    buffer.push((0, "; WARNING: operand byte flagged as data".to_string()));
+                }
+                if let Some(constant) = bank
+                    .get(i + 1)
+                    .and_then(|value| Constant::new(&opcode.addressing, *value))
+                    .filter(|constant| annotations.constants.contains(constant))
+                {
+                    output = constant.operand(&opcode.addressing, options);
+                }
+                if opcode.addressing == Addressing::IndirectY
+                    && annotations.pointers.contains(&operands[0])
+                {
+                    output = format!("({}),Y", constants::pointer_name(operands[0]));
+                }
+                if annotations.oam_page == Some(operands[1])
+                    && matches!(
+                        opcode.addressing,
+                        Addressing::Absolute | Addressing::AbsoluteX | Addressing::AbsoluteY
+                    )
+                {
+                    let name = match operands[0] {
+                        0 => OAM_BUFFER.to_string(),
+                        offset => format!("{OAM_BUFFER}+{}", options.hex(offset as usize, 2)),
+                    };
+                    output = match opcode.addressing {
+                        Addressing::AbsoluteX => format!("{},X", options.absolute(name)),
+                        Addressing::AbsoluteY => format!("{},Y", options.absolute(name)),
+                        _ => options.absolute(name),
+                    };
+                }
+                i += size;
+
+                if let Some(addr) = target {
+                    labels.insert(addr);
+                    if opcode.name == "JSR" {
+                        subroutines.insert(addr);
+                    }
+                }
+
+                let mut line = format!("{indent}{} {}", opcode.name, output);
+                // asm6 has no size suffix and would pick the shorter
+                // zero-page encoding, so the bytes are written as they are
+                if !options.assembler.has_sections()
+                    && operands[1] == 0
+                    && matches!(
+                        opcode.addressing,
+                        Addressing::Absolute | Addressing::AbsoluteX | Addressing::AbsoluteY
+                    )
+                {
+                    let bytes: Vec<String> = bank[(i - size)..=i]
+                        .iter()
+                        .map(|b| options.hex(*b as usize, 2))
+                        .collect();
+                    line = format!("{indent}.db {} ; {}", bytes.join(", "), line.trim());
+                }
+                if options.decode_regs {
+                    let stored = registers::stored_register(opcode);
+                    let description = last_immediate
+                        .filter(|(register, _, next)| {
+                            stored == Some(*register) && *next == g_offset
+                        })
+                        .filter(|_| opcode.addressing == Addressing::Absolute)
+                        .and_then(|(_, value, _)| {
+                            let addr = (operands[1] as usize) << 8 | operands[0] as usize;
+                            registers::describe_write(addr, value)
+                        });
+                    if let Some(description) = description {
+                        line = format!("{} ; {description}", line.trim_end());
+                    }
+
+                    last_immediate = registers::immediate_load(opcode, operands[0])
+                        .map(|(register, value)| (register, value, g_offset + 2));
+                }
+                if opcode.addressing == Addressing::Relative {
+                    let offset = operands[0] as i8;
+                    let mut comments = vec![];
+                    // a wrong decoding, or a branch at the edge of a bank
+                    if target.is_none_or(|target| target >> 16 != id as usize) {
+                        log::warn!("The branch at L{g_offset:06X} goes out of its bank.");
+                        comments.push("out of the bank".into());
+                    }
+                    if options.branch_offsets {
+                        comments.push(offset.to_string());
+                    }
+                    if options.branch_direction {
+                        comments.push(
+                            if offset < 0 {
+                                "backward (loop)"
+                            } else {
+                                "forward"
+                            }
+                            .into(),
+                        );
+                    }
+                    if !comments.is_empty() {
+                        line = format!("{} ; {}", line.trim_end(), comments.join(", "));
+                    }
+                }
+                if options.raw_bytes {
+                    let bytes: Vec<String> = bank[(i - size)..=i]
+                        .iter()
+                        .map(|b| options.hex_digits(*b as usize, 2))
+                        .collect();
+                    line = format!("{} ; {}", line.trim_end(), bytes.join(" "));
+                }
+                buffer.push((g_offset, line));
+                if let Some(target) = target.filter(|target| (target & 0xFFFF) >= 0x8000) {
+                    targets.insert(g_offset, target);
+                }
+
+                if matches!(opcode.name, "RTS" | "RTI" | "JMP") {
+                    buffer.push((0, "".into()));
+                    // only taken by the next code byte, the data in between
+                    // doesn't get a label
+                    print_label = true;
+                }
+                if opcode.name == "RTS" || opcode.name == "RTI" {
+                    routine_starts.insert(buffer.len());
+                }
+            } else {
+                // the NMOS 6502 runs them anyway, the 65C02 as NOPs of its
+                // own which only reassemble as bytes
+                let named = options.opcode_illegals_as_comment && options.cpu != Cpu::Cmos65c02;
+                let comment = if named {
+                    format!("illegal: {}", illegal_name(bank[i]))
+                } else {
+                    "invalid opcode?".to_string()
+                };
+                buffer.push((
+                    g_offset,
+                    format!("{indent}.db {} ; {comment}", options.hex(op, 2)),
+                ));
+                invalid_opcodes += 1;
+            }
+        } else {
+            // is data or unknown, both making a single region
+            let is_data = (cdl[i] & 3) == 2;
+            if !is_inside_data {
+                buffer.push((0, "; start of data".to_string()));
+                is_inside_data = true;
+                word_start = i;
+            }
+            print_label = true;
+
+            if is_data && options.pointer_tables && pointers == 0 && (i - word_start) % 2 == 0 {
+                pointers = count_pointers(bank, cdl, i);
+                if pointers < 2 {
+                    pointers = 0;
+                }
+            }
+
+            if pointers > 0 {
+                // little-endian, the assembler takes care of the order
+                let word = (bank[i + 1] as usize) << 8 | bank[i] as usize;
+                let (operand, target) = word_operand(id, word, rom_data, data_labels, options);
+                labels.extend(target);
+                buffer.push((g_offset, format!("{indent}.dw {operand}")));
+                pointers -= 1;
+                i += 1;
+            } else {
+                match byte_runs.last_mut() {
+                    Some((index, start, len, data))
+                        if *index + *len == buffer.len()
+                            && *start + *len == i
+                            && *data == is_data =>
+                    {
+                        *len += 1
+                    }
+                    _ => byte_runs.push((buffer.len(), i, 1, is_data)),
+                }
+                if options.word_align {
+                    word_start = i + 1;
+                }
+                let comment = if options.strict && !is_data {
+                    " ; unknown"
+                } else {
+                    ""
+                };
+                buffer.push((
+                    g_offset,
+                    format!("{indent}.db {}{comment}", options.hex(bank[i] as usize, 2)),
+                ));
+            }
+        }
+
+        i += 1;
+    }
+
+    if is_inside_data {
+        buffer.push((0, "; end of data".to_string()));
+    }
+
+    // the runs of bytes, split at the labels so they stay at the start of a
+    // line, are written to a file when unknown and long enough, or grouped
+    let g_offset = |i: usize| i + id as usize * 0x10000 + bank_offset;
+    let mut segments = vec![];
+    for (index, start, len, is_data) in byte_runs {
+        let mut first = 0;
+        for k in 1..=len {
+            if k == len || labels.contains(&g_offset(start + k)) {
+                segments.push((index + first, start + first, k - first, is_data));
+                first = k;
+            }
+        }
+    }
+    let mut blobs = vec![];
+    let mut replacements = vec![];
+    for (index, start, len, is_data) in segments {
+        let incbin = !is_data && options.incbin_threshold.is_some_and(|t| len >= t);
+        let mut lines = if incbin {
+            let name = options.bank_path(&format!(
+                "bank{:03}.blob{:02}.bin",
+                options.logical_bank(id),
+                blobs.len()
+            ));
+            log::debug!("{len} unknown bytes of bank {id} written to {name}");
+            let line = (
+                g_offset(start),
+                format!("{indent}{}", options.assembler.incbin(&name)),
+            );
+            blobs.push((name, bank[start..start + len].to_vec()));
+            vec![line]
+        } else if let Some(max) = options.max_line_bytes.filter(|max| *max > 1) {
+            let comment = if options.strict && !is_data {
+                " ; unknown"
+            } else {
+                ""
+            };
+            bank[start..start + len]
+                .chunks(max)
+                .enumerate()
+                .map(|(row, bytes)| {
+                    let bytes: Vec<String> =
+                        bytes.iter().map(|b| options.hex(*b as usize, 2)).collect();
+                    let line = format!("{indent}.db {}{comment}", bytes.join(", "));
+                    (g_offset(start + row * max), line)
+                })
+                .collect()
+        } else if options.hexdump {
+            buffer[index..index + len].to_vec()
+        } else {
+            continue;
+        };
+        if options.hexdump && !incbin {
+            let addr = g_offset(start) & 0xFFFF;
+            let rows = hexdump(&bank[start..start + len], addr, options);
+            lines.splice(0..0, rows.into_iter().map(|row| (0, row)));
+        }
+        replacements.push((index, len, lines));
+    }
+    // from the last, the indexes of the previous ones staying valid
+    for (index, len, lines) in replacements.into_iter().rev() {
+        let (added, removed) = (lines.len(), len);
+        buffer.splice(index..index + len, lines);
+        routine_starts = routine_starts
+            .into_iter()
+            .map(|s| {
+                if s >= index + len {
+                    s + added - removed
+                } else {
+                    s
+                }
+            })
+            .collect();
+    }
+
+    for (index, (addr, _)) in buffer.iter().enumerate() {
+        if subroutines.contains(addr) {
+            // the comments above the routine go with it
+            let mut start = index;
+            while start > 0 && buffer[start - 1].0 == 0 && buffer[start - 1].1.starts_with(';') {
+                start -= 1;
+            }
+            routine_starts.insert(start);
+        }
+    }
+
+    let lines = buffer
+        .into_iter()
+        .enumerate()
+        .map(|(index, (addr, text))| Line {
+            position: (addr != 0).then_some(addr),
+            text,
+            routine_start: routine_starts.contains(&index),
+            target: targets.get(&addr).copied(),
+        })
+        .collect();
+    Ok(BankResult {
+        labels,
+        defined: HashSet::new(),
+        invalid_opcodes,
+        lines,
+        blobs,
+    })
+}
+
+/// Writes a disassembled bank to `bankNNN.asm`, with its blobs and routines,
+/// filling its `defined` labels.
+fn write_prg_bank(
+    id: u8,
+    result: &mut BankResult,
+    rom_data: RomData,
+    cdl: &[u8],
+    data_labels: DataLabels,
+    options: &Options,
+    sink: &mut impl FnMut(&str) -> std::io::Result<Box<dyn Write>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (name, bytes) in &result.blobs {
+        sink(name)?.write_all(bytes)?;
+    }
+
+    let logical = options.logical_bank(id);
+    let mut output = sink(&options.bank_path(&format!("bank{logical:03}.asm")))?;
+    write_bank_header(&mut output, WlaBank::Prg(id), rom_data, options)?;
+    writeln!(output)?;
+    if options.assembler.has_sections() {
+        writeln!(
+            output,
+            "{}.SECTION \"{}\" {}\n",
+            options.indentation(),
+            options.section_name(&format!("Bank{logical}")),
+            options.section_mode
+        )?;
+    }
+
+    let code = cdl.iter().filter(|flags| (*flags & 1) == 1).count();
+    let data = cdl.iter().filter(|flags| (*flags & 3) == 2).count();
+    let unknown = cdl.len() - code - data;
+    let percent = |count: usize| (count * 100 + cdl.len() / 2) / cdl.len();
+    writeln!(
+        output,
+        "; coverage: code={}% data={}% unknown={}%\n",
+        percent(code),
+        percent(data),
+        percent(unknown)
+    )?;
+
+    let mut routine = None;
+    for index in 0..result.lines.len() {
+        if options.split_routines && result.lines[index].routine_start {
+            // named after its first address, the separators having none
+            if let Some(addr) = result.lines[index..].iter().find_map(|line| line.position) {
+                let name = options.bank_path(&format!("{}.asm", options.label(addr, data_labels)));
+                writeln!(
+                    output,
+                    "{}{}",
+                    options.indentation(),
+                    options.assembler.include(&name)
+                )?;
+                routine = Some(sink(&name)?);
+            }
+        }
+
+        let out: &mut dyn Write = match &mut routine {
+            Some(routine) => routine,
+            None => &mut output,
+        };
+        let line = &result.lines[index];
+        if let Some(addr) = line.position.filter(|addr| result.labels.contains(addr)) {
+            result.defined.insert(addr);
+            write_label(out, id, addr, data_labels, options)?;
+        }
+        writeln!(out, "{}", line.text)?;
+    }
+
+    if options.assembler.has_sections() {
+        writeln!(output, "\n{}.ENDS", options.indentation())?;
+    }
+
+    Ok(())
+}
+
+/// Renders `bytes`, the first one at `addr`, as the rows of a hex editor:
+/// `; C000: 48 45 4C 4C 4F ... |HELLO...|`.
+fn hexdump(bytes: &[u8], addr: usize, options: &Options) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(row, bytes)| {
+            let hex: Vec<String> = bytes
+                .iter()
+                .map(|b| options.hex_digits(*b as usize, 2))
+                .collect();
+            let ascii: String = bytes
+                .iter()
+                .map(|b| {
+                    if b.is_ascii_graphic() || *b == b' ' {
+                        *b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!(
+                "; {}: {:<47} |{ascii}|",
+                options.hex_digits(addr + row * 16, 4),
+                hex.join(" ")
+            )
+        })
+        .collect()
+}
+
+/// Writes the label of `addr`, in the bank `id`, on its own line.
+fn write_label(
+    out: &mut dyn Write,
+    id: u8,
+    addr: usize,
+    data_labels: DataLabels,
+    options: &Options,
+) -> io::Result<()> {
+    let label = options.label(addr, data_labels);
+    if options.label_comments {
+        writeln!(out, "{label}: ; {}", options.hex(addr & 0xFFFF, 4))?;
+    } else {
+        writeln!(out, "{label}:")?;
+    }
+    if options.has_local_labels() {
+        writeln!(out, "{}:", options.bank_label(id, addr, data_labels))?;
+    }
+    Ok(())
+}
+
+/// Writes the selected banks, disassembled, one after the other by CPU
+/// address, the addresses between them being reserved with `.ds` (`.dsb`
+/// for asm6).
+fn write_flat(
+    banks: &mut [(usize, u8, BankResult)],
+    rom_data: RomData,
+    data_labels: DataLabels,
+    options: &Options,
+    output: &mut dyn Write,
+) -> io::Result<()> {
+    banks.sort_by_key(|(bank_offset, _, _)| *bank_offset);
+
+    writeln!(output, "; the PRG by CPU address")?;
+    let mut next = 0x8000;
+    for (bank_offset, id, result) in banks.iter() {
+        if *bank_offset > next {
+            write_gap(next, *bank_offset, options, output)?;
+        }
+        writeln!(
+            output,
+            "\n; bank {id} at {}\n",
+            options.hex(*bank_offset, 4)
+        )?;
+        for line in &result.lines {
+            if let Some(addr) = line.position.filter(|addr| result.defined.contains(addr)) {
+                write_label(output, *id, addr, data_labels, options)?;
+            }
+            writeln!(output, "{}", line.text)?;
+        }
+        next = bank_offset + rom_data.bank_size;
+    }
+    if next < 0x10000 {
+        write_gap(next, 0x10000, options, output)?;
+    }
+    Ok(())
+}
+
+fn write_gap(
+    start: usize,
+    end: usize,
+    options: &Options,
+    output: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(
+        output,
+        "\n{}{} {} ; {}-{}",
+        options.indentation(),
+        options.assembler.reserve(),
+        options.hex(end - start, 4),
+        options.hex(start, 4),
+        options.hex(end - 1, 4)
+    )
+}
+
+/// Warns about the ROM labels referenced but never written, which would fail
+/// the assembly.
+fn verify_labels(
+    labels: &BTreeSet<usize>,
+    defined: &HashSet<usize>,
+    data_labels: DataLabels,
+    options: &Options,
+) {
+    // the RAM addresses aren't labels
+    let undefined: Vec<String> = labels
+        .iter()
+        .filter(|label| **label >= 0x8000 && !defined.contains(label))
+        .map(|label| options.label(*label, data_labels))
+        .collect();
+
+    if undefined.is_empty() {
+        log::info!("Every label is defined");
+    } else {
+        log::warn!(
+            "{} labels are referenced but not defined: {}",
+            undefined.len(),
+            undefined.join(", ")
+        );
+    }
+}
+
+/// Returns how many consecutive words starting at `start` are data pointing
+/// into the ROM.
+fn count_pointers(bank: &[u8], cdl: &[u8], start: usize) -> usize {
+    let mut count = 0;
+    let mut i = start;
+    while i + 1 < bank.len() && (cdl[i] & 3) == 2 && (cdl[i + 1] & 3) == 2 && bank[i + 1] >= 0x80 {
+        count += 1;
+        i += 2;
+    }
+
+    count
+}
+
+/// Returns the operand of a `.dw` of `addr` in the bank `id`, a label when
+/// it is in a known bank, with that label.
+fn word_operand(
+    id: u8,
+    addr: usize,
+    rom_data: RomData,
+    data_labels: DataLabels,
+    options: &Options,
+) -> (String, Option<usize>) {
+    if addr >= 0x8000 && !is_in_unknown_bank(id, addr, rom_data) {
+        let target = cpu_to_label(id, addr, rom_data);
+        (options.bank_label(id, target, data_labels), Some(target))
+    } else {
+        (options.hex(addr, 4), None)
+    }
+}
+
+/// A bank of the ROM, as placed in the WLA memory map.
+#[derive(Clone, Copy)]
+enum WlaBank {
+    Header,
+    /// a PRG bank, by its number
+    Prg(u8),
+    /// a CHR window, by its number over all the CHR banks
+    Chr(usize),
+    /// the miscellaneous ROM of NES 2.0, after the given number of CHR
+    /// windows
+    Misc(usize),
+}
+
+/// Returns the WLA bank number and slot of `bank`, and the CPU address it
+/// is placed at with `--org-per-section` (the start of the slot otherwise).
+/// The header is bank 0, followed by the PRG banks, then the CHR windows.
+fn bank_placement(
+    bank: WlaBank,
+    rom_data: RomData,
+    options: &Options,
+) -> (usize, u8, Option<usize>) {
+    match bank {
+        WlaBank::Header => (0, SLOT_HEADER, None),
+        WlaBank::Prg(id) if options.org_per_section => {
+            let bank_offset = get_bank_offset(id, rom_data);
+            let slot = if bank_offset == rom_data.fixed_bank_offset() {
+                SLOT_PRG
+            } else {
+                SLOT_SWAPPABLE
+            };
+            (
+                prg_bank_number(options.logical_bank(id)),
+                slot,
+                Some(bank_offset),
+            )
+        }
+        WlaBank::Prg(id) => (prg_bank_number(options.logical_bank(id)), SLOT_PRG, None),
+        WlaBank::Chr(window) => (
+            chr_bank_number(window, rom_data.banks_count),
+            SLOT_CHR,
+            None,
+        ),
+        WlaBank::Misc(windows) => (
+            chr_bank_number(windows, rom_data.banks_count),
+            SLOT_MISC,
+            None,
+        ),
+    }
+}
+
+/// Writes the `.BANK` and `.ORG` (or `.ORGA`) of `bank`. For asm6, which
+/// writes the ROM in order, only the `.base` of the PRG banks.
+fn write_bank_header(
+    out: &mut dyn Write,
+    bank: WlaBank,
+    rom_data: RomData,
+    options: &Options,
+) -> io::Result<()> {
+    if !options.assembler.has_sections() {
+        return match bank {
+            WlaBank::Prg(id) => writeln!(
+                out,
+                "{}.base {}",
+                options.indentation(),
+                options.hex(get_bank_offset(id, rom_data), 4)
+            ),
+            _ => Ok(()),
+        };
+    }
+    let (number, slot, org) = bank_placement(bank, rom_data, options);
+    let indent = options.indentation();
+    writeln!(out, "{indent}.BANK {number} SLOT {slot}")?;
+    match org {
+        Some(addr) => writeln!(out, "{indent}.ORGA {}", options.hex(addr, 4)),
+        None => writeln!(out, "{indent}.ORG {}", options.hex(0, 4)),
+    }
+}
+
+/// Whether the save RAM variables go in a `.RAMSECTION` of their own, when
+/// the RAM is kept by a battery.
+fn has_sram_section(header: &Header, options: &Options) -> bool {
+    options.wram_vars && header.prg_ram && header.battery && options.assembler.has_sections()
+}
+
+/// The number of the WLA bank of a PRG bank, the header being bank 0.
+fn prg_bank_number(id: u8) -> usize {
+    id as usize + 1
+}
+
+/// The number of the WLA bank of a CHR window, the CHR banks following the
+/// PRG ones. Computed in `usize` since there can be 255 of each.
+fn chr_bank_number(window: usize, prg_banks_count: u8) -> usize {
+    prg_bank_number(prg_banks_count) + window
+}
+
+/// The number of CHR windows written, none when the CHR banks are skipped
+/// (they are still read).
+fn written_chr_windows(chr_banks_count: u8, mapper: u8, options: &Options) -> usize {
+    if options.skips_chr() {
+        0
+    } else {
+        chr_banks_count as usize * CHR_SIZE / chr_window_size(mapper)
+    }
+}
+
+/// The size of the CHR windows the mapper switches, the CHR banks being
+/// split in as many WLA banks.
+fn chr_window_size(mapper: u8) -> usize {
+    match mapper {
+        // MMC3 and MMC5
+        4 | 5 => 0x400,
+        // MMC1, and MMC2 and MMC4 with their latches
+        1 | 9 | 10 => 0x1000,
+        _ => CHR_SIZE,
+    }
+}
+
+fn is_supported_mapper(mapper: u8) -> bool {
+    mapper == 10
+}
+
+/// The CPU address of a bank: the last one is fixed at the end of the
+/// memory, `$C000` for 16KB banks (also the only bank of a 16KB NROM,
+/// mirrored at `$8000`), the others are swapped in at `$8000`.
+fn get_bank_offset(bank: u8, rom_data: RomData) -> usize {
+    if let Some(base) = rom_data.base {
+        return base;
+    }
+
+    match rom_data.mapper {
+        10 if bank == rom_data.banks_count - 1 => rom_data.fixed_bank_offset(),
+        10 => 0x8000,
+        // unsupported, this is a guess that holds for NROM and UxROM
+        _ if bank == rom_data.banks_count - 1 => rom_data.fixed_bank_offset(),
+        _ => 0x8000,
+    }
+}
+
+fn write_addressing(
+    addressing: &Addressing,
+    bank: &[u8],
+    id: u8,
+    position: usize,
+    rom_data: RomData,
+    data_labels: DataLabels,
+    options: &Options,
+) -> Result<(usize, String, Option<usize>), Box<dyn std::error::Error>> {
+    let (operand, target) = match addressing {
+        Addressing::Absolute => get_target(id, bank[0], bank[1], rom_data, data_labels, options),
+        Addressing::AbsoluteX => {
+            let (label, target) = get_target(id, bank[0], bank[1], rom_data, data_labels, options);
+            (format!("{label},X"), target)
+        }
+        Addressing::AbsoluteXIndirect => {
+            let pointer = (bank[1] as usize) << 8 | bank[0] as usize;
+            (format!("({},X)", options.hex(pointer, 4)), None)
+        }
+        Addressing::AbsoluteY => {
+            let (label, target) = get_target(id, bank[0], bank[1], rom_data, data_labels, options);
+            (format!("{label},Y"), target)
+        }
+        Addressing::Accumulator => (options.assembler.accumulator().into(), None),
+        Addressing::Immediate => (format!("#{}", bank[0]), None),
+        Addressing::Implied => ("".into(), None),
+        Addressing::Indirect => {
+            let pointer = (bank[1] as usize) << 8 | bank[0] as usize;
+            (format!("({})", options.hex(pointer, 4)), None)
+        }
+        Addressing::IndirectY => (format!("({}),Y", options.hex(bank[0] as usize, 2)), None),
+        Addressing::Relative => {
+            let offset = bank[0] as i8 as isize;
+            let target = (position as isize + offset + 2) as usize;
+            // out of its bank, it goes to whatever bank is mapped there
+            let addr = ((position & 0xFFFF) as isize + offset + 2) as usize & 0xFFFF;
+            if target >> 16 == id as usize && prg_offset(target, rom_data).is_some() {
+                (options.bank_label(id, target, data_labels), Some(target))
+            } else if addr >= 0x8000 && !is_in_unknown_bank(id, addr, rom_data) {
+                let target = cpu_to_label(id, addr, rom_data);
+                (options.bank_label(id, target, data_labels), Some(target))
+            } else {
+                (options.hex(addr, 4), None)
+            }
+        }
+        Addressing::XIndirect => (format!("({},X)", options.hex(bank[0] as usize, 2)), None),
+        Addressing::ZeroPage => (options.zero_page(options.hex(bank[0] as usize, 2)), None),
+        Addressing::ZeroPageIndirect => (format!("({})", options.hex(bank[0] as usize, 2)), None),
+        Addressing::ZeroPageX => (
+            format!("{},X", options.zero_page(options.hex(bank[0] as usize, 2))),
+            None,
+        ),
+        Addressing::ZeroPageY => (
+            format!("{},Y", options.zero_page(options.hex(bank[0] as usize, 2))),
+            None,
+        ),
+    };
+    Ok((addressing.operand_len(), operand, target))
+}
+
+fn get_target(
+    id: u8,
+    lo: u8,
+    hi: u8,
+    rom_data: RomData,
+    data_labels: DataLabels,
+    options: &Options,
+) -> (String, Option<usize>) {
+    let addr = ((hi as usize) << 8) + (lo as usize);
+
+    if options.wram_vars && is_wram(addr) {
+        log::debug!("${addr:04X} is a save RAM variable");
+        return (options.absolute(wram_label(addr)), Some(addr));
+    }
+
+    // below the PRG: the RAM and its mirrors, the registers and the save RAM
+    if addr < 0x8000 {
+        log::debug!("${addr:04X} isn't in the PRG");
+        let operand = options.hex(addr, 4);
+        // the size is always explicit in the zero page, or the assembler
+        // would pick the shorter zero-page encoding
+        let operand = if addr < 0x100 {
+            options.assembler.word(operand)
+        } else {
+            options.absolute(operand)
+        };
+        return (operand, Some(addr));
+    }
+
+    if is_in_unknown_bank(id, addr, rom_data) {
+        log::debug!("${addr:04X} from bank {id} is in an unknown bank");
+        return (options.absolute(options.hex(addr, 4)), None);
+    }
+
+    let target = cpu_to_label(id, addr, rom_data);
+    log::debug!("${addr:04X} from bank {id} resolved to L{target:06X}");
+
+    (
+        options
+            .assembler
+            .word(options.bank_label(id, target, data_labels)),
+        Some(target),
+    )
+}
+
+/// Whether `addr`, seen from bank `id`, is in the swappable slot while `id`
+/// isn't, so any bank could be there (e.g. the fixed bank of MMC4 reading
+/// `$8000-$BFFF`).
+fn is_in_unknown_bank(id: u8, addr: usize, rom_data: RomData) -> bool {
+    addr >= 0x8000 && prg_offset(cpu_to_label(id, addr, rom_data), rom_data).is_none()
+}
+
+/// Returns the label of a ROM address seen from bank `id`: in `id` itself,
+/// else in the last bank, fixed at the end of the memory (MMC4 = last bank
+/// is fixed at $C000-FFFF).
+fn cpu_to_label(id: u8, addr: usize, rom_data: RomData) -> usize {
+    let own = ((id as usize) << 16) + addr;
+    let bank = if prg_offset(own, rom_data).is_none() && addr >= rom_data.fixed_bank_offset() {
+        rom_data.banks_count - 1
+    } else {
+        id
+    };
+
+    ((bank as usize) << 16) + addr
+}
+
+/// Returns the label of a location given by the user, failing if it isn't in
+/// a bank.
+fn resolve_location(
+    location: Location,
+    rom_data: RomData,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    location_to_label(location, rom_data).ok_or_else(|| {
+        Box::new(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{location} is not in the PRG."),
+        )) as Box<dyn std::error::Error>
+    })
+}
+
+/// Returns the label of a location given by the user, if it is in a bank.
+fn location_to_label(location: Location, rom_data: RomData) -> Option<usize> {
+    let label = match location {
+        Location::Cpu(addr) => cpu_to_label(0, addr as usize, rom_data),
+        Location::Bank(id, offset) => {
+            if id >= rom_data.banks_count {
+                return None;
+            }
+            ((id as usize) << 16) + get_bank_offset(id, rom_data) + offset as usize
+        }
+    };
+
+    prg_offset(label, rom_data).map(|_| label)
+}
+
+/// Returns the label of an offset in the PRG.
+fn offset_to_label(offset: usize, rom_data: RomData) -> usize {
+    let id = offset / rom_data.bank_size;
+    let bank_offset = get_bank_offset(id as u8, rom_data);
+    (id << 16) + bank_offset + offset % rom_data.bank_size
+}
+
+/// Returns the offset in the PRG of a label, if it is inside a bank.
+fn prg_offset(label: usize, rom_data: RomData) -> Option<usize> {
+    let id = label >> 16;
+    let addr = label & 0xFFFF;
+    if id >= rom_data.banks_count as usize {
+        return None;
+    }
+
+    let bank_offset = get_bank_offset(id as u8, rom_data);
+    if addr < bank_offset || addr >= bank_offset + rom_data.bank_size {
+        return None;
+    }
+
+    Some(id * rom_data.bank_size + addr - bank_offset)
+}
+
+/// An instruction decoded from the PRG.
+struct Instruction {
+    position: usize,
+    opcode: &'static Opcode,
+    /// number of bytes after the opcode
+    size: usize,
+    /// raw value of those bytes
+    value: usize,
+    operand: String,
+    target: Option<usize>,
+}
+
+impl Instruction {
+    /// Returns the target if it is in ROM.
+    fn rom_target(&self) -> Option<usize> {
+        self.target.filter(|target| (target & 0xFFFF) >= 0x8000)
+    }
+
+    fn next(&self) -> usize {
+        self.position + self.size + 1
+    }
+}
+
+/// Decodes the instruction at `position` (a label), if it's a valid opcode
+/// that doesn't overlap two banks.
+fn decode_one(
+    prg: &[u8],
+    position: usize,
+    rom_data: RomData,
+    data_labels: DataLabels,
+    options: &Options,
+) -> Option<Instruction> {
+    let offset = prg_offset(position, rom_data)?;
+    let opcode = options.cpu.decode(prg[offset])?;
+
+    let operands = [
+        prg.get(offset + 1).copied().unwrap_or(0),
+        prg.get(offset + 2).copied().unwrap_or(0),
+    ];
+    let id = (position >> 16) as u8;
+    let (size, operand, target) = write_addressing(
+        &opcode.addressing,
+        &operands,
+        id,
+        position,
+        rom_data,
+        data_labels,
+        options,
+    )
+    .ok()?;
+
+    if offset % rom_data.bank_size + size >= rom_data.bank_size {
+        return None;
+    }
+
+    let value = match size {
+        1 => operands[0] as usize,
+        2 => (operands[1] as usize) << 8 | operands[0] as usize,
+        _ => 0,
+    };
+
+    Some(Instruction {
+        position,
+        opcode,
+        size,
+        value,
+        operand,
+        target,
+    })
+}
+
+fn is_wram(addr: usize) -> bool {
+    (0x6000..0x8000).contains(&addr)
+}
+
+fn wram_label(addr: usize) -> String {
+    format!("sram_{:04X}", addr - 0x6000)
+}
+
+/// The addressing modes of the 6502.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Addressing {
+    Absolute,
+    AbsoluteX,
+    /// `($1234,X)`, the `JMP` of the 65C02
+    AbsoluteXIndirect,
+    AbsoluteY,
+    Accumulator,
+    Immediate,
+    Implied,
+    Indirect,
+    IndirectY,
+    Relative,
+    XIndirect,
+    ZeroPage,
+    /// `($12)`, added by the 65C02
+    ZeroPageIndirect,
+    ZeroPageX,
+    ZeroPageY,
+}
+
+impl Addressing {
+    /// Number of bytes following the opcode.
+    pub fn operand_len(&self) -> usize {
+        match self {
+            Addressing::Accumulator | Addressing::Implied => 0,
+            Addressing::Immediate
+            | Addressing::IndirectY
+            | Addressing::Relative
+            | Addressing::XIndirect
+            | Addressing::ZeroPage
+            | Addressing::ZeroPageIndirect
+            | Addressing::ZeroPageX
+            | Addressing::ZeroPageY => 1,
+            Addressing::Absolute
+            | Addressing::AbsoluteX
+            | Addressing::AbsoluteXIndirect
+            | Addressing::AbsoluteY
+            | Addressing::Indirect => 2,
+        }
+    }
+}
+
+/// An instruction of the 6502, without its operand.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Opcode {
+    pub name: &'static str,
+    pub addressing: Addressing,
+}
+
+/// Returns the instruction encoded by `byte`, `None` for the illegal opcodes.
+/// `Cpu::decode` does the same for the other variants of the 6502.
+pub fn decode_opcode(byte: u8) -> Option<&'static Opcode> {
+    OPCODES[byte as usize].as_ref()
+}
+
+/// The official instructions of the NMOS 6502, the CPU of the NES.
+static OPCODES: [Option<Opcode>; 256] = NMOS_OPCODES;
+
+/// The 65C02 adds `BRA`, `STZ`, `PHX`... and the `($12)` addressing. Its
+/// other opcodes are `NOP`s of 1 to 3 bytes, left `None` here since an
+/// assembler would write them back as `$EA`.
+static CMOS_OPCODES: [Option<Opcode>; 256] = with_opcodes(
+    NMOS_OPCODES,
+    &[
+        (0x04, "TSB", Addressing::ZeroPage),
+        (0x0C, "TSB", Addressing::Absolute),
+        (0x12, "ORA", Addressing::ZeroPageIndirect),
+        (0x14, "TRB", Addressing::ZeroPage),
+        (0x1A, "INC", Addressing::Accumulator),
+        (0x1C, "TRB", Addressing::Absolute),
+        (0x32, "AND", Addressing::ZeroPageIndirect),
+        (0x34, "BIT", Addressing::ZeroPageX),
+        (0x3A, "DEC", Addressing::Accumulator),
+        (0x3C, "BIT", Addressing::AbsoluteX),
+        (0x52, "EOR", Addressing::ZeroPageIndirect),
+        (0x5A, "PHY", Addressing::Implied),
+        (0x64, "STZ", Addressing::ZeroPage),
+        (0x72, "ADC", Addressing::ZeroPageIndirect),
+        (0x74, "STZ", Addressing::ZeroPageX),
+        (0x7A, "PLY", Addressing::Implied),
+        (0x7C, "JMP", Addressing::AbsoluteXIndirect),
+        (0x80, "BRA", Addressing::Relative),
+        (0x89, "BIT", Addressing::Immediate),
+        (0x92, "STA", Addressing::ZeroPageIndirect),
+        (0x9C, "STZ", Addressing::Absolute),
+        (0x9E, "STZ", Addressing::AbsoluteX),
+        (0xB2, "LDA", Addressing::ZeroPageIndirect),
+        (0xD2, "CMP", Addressing::ZeroPageIndirect),
+        (0xDA, "PHX", Addressing::Implied),
+        (0xF2, "SBC", Addressing::ZeroPageIndirect),
+        (0xFA, "PLX", Addressing::Implied),
+    ],
+);
+
+/// The stable illegal opcodes of the NMOS 6502, named as in ca65. The ones
+/// whose result depends on the chip (`ANE`, `SHA`, `LAS`...) and the ones
+/// halting the CPU stay invalid.
+static ILLEGAL_OPCODES: [Option<Opcode>; 256] = with_opcodes(
+    NMOS_OPCODES,
+    &[
+        (0x03, "SLO", Addressing::XIndirect),
+        (0x04, "NOP", Addressing::ZeroPage),
+        (0x07, "SLO", Addressing::ZeroPage),
+        (0x0B, "ANC", Addressing::Immediate),
+        (0x0C, "NOP", Addressing::Absolute),
+        (0x0F, "SLO", Addressing::Absolute),
+        (0x13, "SLO", Addressing::IndirectY),
+        (0x14, "NOP", Addressing::ZeroPageX),
+        (0x17, "SLO", Addressing::ZeroPageX),
+        (0x1A, "NOP", Addressing::Implied),
+        (0x1B, "SLO", Addressing::AbsoluteY),
+        (0x1C, "NOP", Addressing::AbsoluteX),
+        (0x1F, "SLO", Addressing::AbsoluteX),
+        (0x23, "RLA", Addressing::XIndirect),
+        (0x27, "RLA", Addressing::ZeroPage),
+        (0x2B, "ANC", Addressing::Immediate),
+        (0x2F, "RLA", Addressing::Absolute),
+        (0x33, "RLA", Addressing::IndirectY),
+        (0x34, "NOP", Addressing::ZeroPageX),
+        (0x37, "RLA", Addressing::ZeroPageX),
+        (0x3A, "NOP", Addressing::Implied),
+        (0x3B, "RLA", Addressing::AbsoluteY),
+        (0x3C, "NOP", Addressing::AbsoluteX),
+        (0x3F, "RLA", Addressing::AbsoluteX),
+        (0x43, "SRE", Addressing::XIndirect),
+        (0x44, "NOP", Addressing::ZeroPage),
+        (0x47, "SRE", Addressing::ZeroPage),
+        (0x4B, "ALR", Addressing::Immediate),
+        (0x4F, "SRE", Addressing::Absolute),
+        (0x53, "SRE", Addressing::IndirectY),
+        (0x54, "NOP", Addressing::ZeroPageX),
+        (0x57, "SRE", Addressing::ZeroPageX),
+        (0x5A, "NOP", Addressing::Implied),
+        (0x5B, "SRE", Addressing::AbsoluteY),
+        (0x5C, "NOP", Addressing::AbsoluteX),
+        (0x5F, "SRE", Addressing::AbsoluteX),
+        (0x63, "RRA", Addressing::XIndirect),
+        (0x64, "NOP", Addressing::ZeroPage),
+        (0x67, "RRA", Addressing::ZeroPage),
+        (0x6B, "ARR", Addressing::Immediate),
+        (0x6F, "RRA", Addressing::Absolute),
+        (0x73, "RRA", Addressing::IndirectY),
+        (0x74, "NOP", Addressing::ZeroPageX),
+        (0x77, "RRA", Addressing::ZeroPageX),
+        (0x7A, "NOP", Addressing::Implied),
+        (0x7B, "RRA", Addressing::AbsoluteY),
+        (0x7C, "NOP", Addressing::AbsoluteX),
+        (0x7F, "RRA", Addressing::AbsoluteX),
+        (0x80, "NOP", Addressing::Immediate),
+        (0x82, "NOP", Addressing::Immediate),
+        (0x83, "SAX", Addressing::XIndirect),
+        (0x87, "SAX", Addressing::ZeroPage),
+        (0x89, "NOP", Addressing::Immediate),
+        (0x8F, "SAX", Addressing::Absolute),
+        (0x97, "SAX", Addressing::ZeroPageY),
+        (0xA3, "LAX", Addressing::XIndirect),
+        (0xA7, "LAX", Addressing::ZeroPage),
+        (0xAF, "LAX", Addressing::Absolute),
+        (0xB3, "LAX", Addressing::IndirectY),
+        (0xB7, "LAX", Addressing::ZeroPageY),
+        (0xBF, "LAX", Addressing::AbsoluteY),
+        (0xC2, "NOP", Addressing::Immediate),
+        (0xC3, "DCP", Addressing::XIndirect),
+        (0xC7, "DCP", Addressing::ZeroPage),
+        (0xCB, "AXS", Addressing::Immediate),
+        (0xCF, "DCP", Addressing::Absolute),
+        (0xD3, "DCP", Addressing::IndirectY),
+        (0xD4, "NOP", Addressing::ZeroPageX),
+        (0xD7, "DCP", Addressing::ZeroPageX),
+        (0xDA, "NOP", Addressing::Implied),
+        (0xDB, "DCP", Addressing::AbsoluteY),
+        (0xDC, "NOP", Addressing::AbsoluteX),
+        (0xDF, "DCP", Addressing::AbsoluteX),
+        (0xE2, "NOP", Addressing::Immediate),
+        (0xE3, "ISC", Addressing::XIndirect),
+        (0xE7, "ISC", Addressing::ZeroPage),
+        (0xEB, "SBC", Addressing::Immediate),
+        (0xEF, "ISC", Addressing::Absolute),
+        (0xF3, "ISC", Addressing::IndirectY),
+        (0xF4, "NOP", Addressing::ZeroPageX),
+        (0xF7, "ISC", Addressing::ZeroPageX),
+        (0xFA, "NOP", Addressing::Implied),
+        (0xFB, "ISC", Addressing::AbsoluteY),
+        (0xFC, "NOP", Addressing::AbsoluteX),
+        (0xFF, "ISC", Addressing::AbsoluteX),
+    ],
+);
+
+/// The illegal opcodes of the NMOS 6502 left out of `ILLEGAL_OPCODES`,
+/// named for `--opcode-illegals-as-comment`.
+const UNSTABLE_OPCODES: [(u8, &str); 20] = [
+    (0x02, "JAM"),
+    (0x12, "JAM"),
+    (0x22, "JAM"),
+    (0x32, "JAM"),
+    (0x42, "JAM"),
+    (0x52, "JAM"),
+    (0x62, "JAM"),
+    (0x72, "JAM"),
+    (0x8B, "ANE"),
+    (0x92, "JAM"),
+    (0x93, "SHA"),
+    (0x9B, "TAS"),
+    (0x9C, "SHY"),
+    (0x9E, "SHX"),
+    (0x9F, "SHA"),
+    (0xAB, "LXA"),
+    (0xB2, "JAM"),
+    (0xBB, "LAS"),
+    (0xD2, "JAM"),
+    (0xF2, "JAM"),
+];
+
+/// Returns the name of the illegal instruction of the NMOS 6502 encoded by
+/// `byte`, or of the official one.
+fn illegal_name(byte: u8) -> &'static str {
+    match &ILLEGAL_OPCODES[byte as usize] {
+        Some(opcode) => opcode.name,
+        None => UNSTABLE_OPCODES
+            .iter()
+            .find(|(opcode, _)| *opcode == byte)
+            .map_or("???", |(_, name)| name),
+    }
+}
+
+/// Adds the `opcodes` to `table`, a `const fn` to build the tables of the
+/// variants at compile time.
+const fn with_opcodes(
+    mut table: [Option<Opcode>; 256],
+    opcodes: &[(u8, &'static str, Addressing)],
+) -> [Option<Opcode>; 256] {
+    let mut i = 0;
+    while i < opcodes.len() {
+        let (byte, name, addressing) = opcodes[i];
+        table[byte as usize] = Some(Opcode { name, addressing });
+        i += 1;
+    }
+    table
+}
+
+const NMOS_OPCODES: [Option<Opcode>; 256] = [
+    Some(Opcode {
+        name: "BRK",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "ORA",
+        addressing: Addressing::XIndirect,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "ORA",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "ASL",
+        addressing: Addressing::ZeroPage,
+    }),
+    None,
+    Some(Opcode {
+        name: "PHP",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "ORA",
+        addressing: Addressing::Immediate,
+    }),
+    Some(Opcode {
+        name: "ASL",
+        addressing: Addressing::Accumulator,
+    }),
+    None,
+    None,
+    Some(Opcode {
+        name: "ORA",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "ASL",
+        addressing: Addressing::Absolute,
+    }),
+    None,
+    Some(Opcode {
+        name: "BPL",
+        addressing: Addressing::Relative,
+    }),
+    Some(Opcode {
+        name: "ORA",
+        addressing: Addressing::IndirectY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "ORA",
+        addressing: Addressing::ZeroPageX,
+    }),
+    Some(Opcode {
+        name: "ASL",
+        addressing: Addressing::ZeroPageX,
+    }),
+    None,
+    Some(Opcode {
+        name: "CLC",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "ORA",
+        addressing: Addressing::AbsoluteY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "ORA",
+        addressing: Addressing::AbsoluteX,
+    }),
+    Some(Opcode {
+        name: "ASL",
+        addressing: Addressing::AbsoluteX,
+    }),
+    None,
+    Some(Opcode {
+        name: "JSR",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "AND",
+        addressing: Addressing::XIndirect,
+    }),
+    None,
+    None,
+    Some(Opcode {
+        name: "BIT",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "AND",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "ROL",
+        addressing: Addressing::ZeroPage,
+    }),
+    None,
+    Some(Opcode {
+        name: "PLP",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "AND",
+        addressing: Addressing::Immediate,
+    }),
+    Some(Opcode {
+        name: "ROL",
+        addressing: Addressing::Accumulator,
+    }),
+    None,
+    Some(Opcode {
+        name: "BIT",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "AND",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "ROL",
+        addressing: Addressing::Absolute,
+    }),
+    None,
+    Some(Opcode {
+        name: "BMI",
+        addressing: Addressing::Relative,
+    }),
+    Some(Opcode {
+        name: "AND",
+        addressing: Addressing::IndirectY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "AND",
+        addressing: Addressing::ZeroPageX,
+    }),
+    Some(Opcode {
+        name: "ROL",
+        addressing: Addressing::ZeroPageX,
+    }),
+    None,
+    Some(Opcode {
+        name: "SEC",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "AND",
+        addressing: Addressing::AbsoluteY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "AND",
+        addressing: Addressing::AbsoluteX,
+    }),
+    Some(Opcode {
+        name: "ROL",
+        addressing: Addressing::AbsoluteX,
+    }),
+    None,
+    Some(Opcode {
+        name: "RTI",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "EOR",
+        addressing: Addressing::XIndirect,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "EOR",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "LSR",
+        addressing: Addressing::ZeroPage,
+    }),
+    None,
+    Some(Opcode {
+        name: "PHA",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "EOR",
+        addressing: Addressing::Immediate,
+    }),
+    Some(Opcode {
+        name: "LSR",
+        addressing: Addressing::Accumulator,
+    }),
+    None,
+    Some(Opcode {
+        name: "JMP",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "EOR",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "LSR",
+        addressing: Addressing::Absolute,
+    }),
+    None,
+    Some(Opcode {
+        name: "BVC",
+        addressing: Addressing::Relative,
+    }),
+    Some(Opcode {
+        name: "EOR",
+        addressing: Addressing::IndirectY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "EOR",
+        addressing: Addressing::ZeroPageX,
+    }),
+    Some(Opcode {
+        name: "LSR",
+        addressing: Addressing::ZeroPageX,
+    }),
+    None,
+    Some(Opcode {
+        name: "CLI",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "EOR",
+        addressing: Addressing::AbsoluteY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "EOR",
+        addressing: Addressing::AbsoluteX,
+    }),
+    Some(Opcode {
+        name: "LSR",
+        addressing: Addressing::AbsoluteX,
+    }),
+    None,
+    Some(Opcode {
+        name: "RTS",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "ADC",
+        addressing: Addressing::XIndirect,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "ADC",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "ROR",
+        addressing: Addressing::ZeroPage,
+    }),
+    None,
+    Some(Opcode {
+        name: "PLA",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "ADC",
+        addressing: Addressing::Immediate,
+    }),
+    Some(Opcode {
+        name: "ROR",
+        addressing: Addressing::Accumulator,
+    }),
+    None,
+    Some(Opcode {
+        name: "JMP",
+        addressing: Addressing::Indirect,
+    }),
+    Some(Opcode {
+        name: "ADC",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "ROR",
+        addressing: Addressing::Absolute,
+    }),
+    None,
+    Some(Opcode {
+        name: "BVS",
+        addressing: Addressing::Relative,
+    }),
+    Some(Opcode {
+        name: "ADC",
+        addressing: Addressing::IndirectY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "ADC",
+        addressing: Addressing::ZeroPageX,
+    }),
+    Some(Opcode {
+        name: "ROR",
+        addressing: Addressing::ZeroPageX,
+    }),
+    None,
+    Some(Opcode {
+        name: "SEI",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "ADC",
+        addressing: Addressing::AbsoluteY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "ADC",
+        addressing: Addressing::AbsoluteX,
+    }),
+    Some(Opcode {
+        name: "ROR",
+        addressing: Addressing::AbsoluteX,
+    }),
+    None,
+    None,
+    Some(Opcode {
+        name: "STA",
+        addressing: Addressing::XIndirect,
+    }),
+    None,
+    None,
+    Some(Opcode {
+        name: "STY",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "STA",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "STX",
+        addressing: Addressing::ZeroPage,
+    }),
+    None,
+    Some(Opcode {
+        name: "DEY",
+        addressing: Addressing::Implied,
+    }),
+    None,
+    Some(Opcode {
+        name: "TXA",
+        addressing: Addressing::Implied,
+    }),
+    None,
+    Some(Opcode {
+        name: "STY",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "STA",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "STX",
+        addressing: Addressing::Absolute,
+    }),
+    None,
+    Some(Opcode {
+        name: "BCC",
+        addressing: Addressing::Relative,
+    }),
+    Some(Opcode {
+        name: "STA",
+        addressing: Addressing::IndirectY,
+    }),
+    None,
+    None,
+    Some(Opcode {
+        name: "STY",
+        addressing: Addressing::ZeroPageX,
+    }),
+    Some(Opcode {
+        name: "STA",
+        addressing: Addressing::ZeroPageX,
+    }),
+    Some(Opcode {
+        name: "STX",
+        addressing: Addressing::ZeroPageY,
+    }),
+    None,
+    Some(Opcode {
+        name: "TYA",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "STA",
+        addressing: Addressing::AbsoluteY,
+    }),
+    Some(Opcode {
+        name: "TXS",
+        addressing: Addressing::Implied,
+    }),
+    None,
+    None,
+    Some(Opcode {
+        name: "STA",
+        addressing: Addressing::AbsoluteX,
+    }),
+    None,
+    None,
+    Some(Opcode {
+        name: "LDY",
+        addressing: Addressing::Immediate,
+    }),
+    Some(Opcode {
+        name: "LDA",
+        addressing: Addressing::XIndirect,
+    }),
+    Some(Opcode {
+        name: "LDX",
+        addressing: Addressing::Immediate,
+    }),
+    None,
+    Some(Opcode {
+        name: "LDY",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "LDA",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "LDX",
+        addressing: Addressing::ZeroPage,
+    }),
+    None,
+    Some(Opcode {
+        name: "TAY",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "LDA",
+        addressing: Addressing::Immediate,
+    }),
+    Some(Opcode {
+        name: "TAX",
+        addressing: Addressing::Implied,
+    }),
+    None,
+    Some(Opcode {
+        name: "LDY",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "LDA",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "LDX",
+        addressing: Addressing::Absolute,
+    }),
+    None,
+    Some(Opcode {
+        name: "BCS",
+        addressing: Addressing::Relative,
+    }),
+    Some(Opcode {
+        name: "LDA",
+        addressing: Addressing::IndirectY,
+    }),
+    None,
+    None,
+    Some(Opcode {
+        name: "LDY",
+        addressing: Addressing::ZeroPageX,
+    }),
+    Some(Opcode {
+        name: "LDA",
+        addressing: Addressing::ZeroPageX,
+    }),
+    Some(Opcode {
+        name: "LDX",
+        addressing: Addressing::ZeroPageY,
+    }),
+    None,
+    Some(Opcode {
+        name: "CLV",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "LDA",
+        addressing: Addressing::AbsoluteY,
+    }),
+    Some(Opcode {
+        name: "TSX",
+        addressing: Addressing::Implied,
+    }),
+    None,
+    Some(Opcode {
+        name: "LDY",
+        addressing: Addressing::AbsoluteX,
+    }),
+    Some(Opcode {
+        name: "LDA",
+        addressing: Addressing::AbsoluteX,
+    }),
+    Some(Opcode {
+        name: "LDX",
+        addressing: Addressing::AbsoluteY,
+    }),
+    None,
+    Some(Opcode {
+        name: "CPY",
+        addressing: Addressing::Immediate,
+    }),
+    Some(Opcode {
+        name: "CMP",
+        addressing: Addressing::XIndirect,
+    }),
+    None,
+    None,
+    Some(Opcode {
+        name: "CPY",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "CMP",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "DEC",
+        addressing: Addressing::ZeroPage,
+    }),
+    None,
+    Some(Opcode {
+        name: "INY",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "CMP",
+        addressing: Addressing::Immediate,
+    }),
+    Some(Opcode {
+        name: "DEX",
+        addressing: Addressing::Implied,
+    }),
+    None,
+    Some(Opcode {
+        name: "CPY",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "CMP",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "DEC",
+        addressing: Addressing::Absolute,
+    }),
+    None,
+    Some(Opcode {
+        name: "BNE",
+        addressing: Addressing::Relative,
+    }),
+    Some(Opcode {
+        name: "CMP",
+        addressing: Addressing::IndirectY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "CMP",
+        addressing: Addressing::ZeroPageX,
+    }),
+    Some(Opcode {
+        name: "DEC",
+        addressing: Addressing::ZeroPageX,
+    }),
+    None,
+    Some(Opcode {
+        name: "CLD",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "CMP",
+        addressing: Addressing::AbsoluteY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "CMP",
+        addressing: Addressing::AbsoluteX,
+    }),
+    Some(Opcode {
+        name: "DEC",
+        addressing: Addressing::AbsoluteX,
+    }),
+    None,
+    Some(Opcode {
+        name: "CPX",
+        addressing: Addressing::Immediate,
+    }),
+    Some(Opcode {
+        name: "SBC",
+        addressing: Addressing::XIndirect,
+    }),
+    None,
+    None,
+    Some(Opcode {
+        name: "CPX",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "SBC",
+        addressing: Addressing::ZeroPage,
+    }),
+    Some(Opcode {
+        name: "INC",
+        addressing: Addressing::ZeroPage,
+    }),
+    None,
+    Some(Opcode {
+        name: "INX",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "SBC",
+        addressing: Addressing::Immediate,
+    }),
+    Some(Opcode {
+        name: "NOP",
+        addressing: Addressing::Implied,
+    }),
+    None,
+    Some(Opcode {
+        name: "CPX",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "SBC",
+        addressing: Addressing::Absolute,
+    }),
+    Some(Opcode {
+        name: "INC",
+        addressing: Addressing::Absolute,
+    }),
+    None,
+    Some(Opcode {
+        name: "BEQ",
+        addressing: Addressing::Relative,
+    }),
+    Some(Opcode {
+        name: "SBC",
+        addressing: Addressing::IndirectY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "SBC",
+        addressing: Addressing::ZeroPageX,
+    }),
+    Some(Opcode {
+        name: "INC",
+        addressing: Addressing::ZeroPageX,
+    }),
+    None,
+    Some(Opcode {
+        name: "SED",
+        addressing: Addressing::Implied,
+    }),
+    Some(Opcode {
+        name: "SBC",
+        addressing: Addressing::AbsoluteY,
+    }),
+    None,
+    None,
+    None,
+    Some(Opcode {
+        name: "SBC",
+        addressing: Addressing::AbsoluteX,
+    }),
+    Some(Opcode {
+        name: "INC",
+        addressing: Addressing::AbsoluteX,
+    }),
+    None,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::ops::Range;
+
+    /// The iNES header of the test ROMs: mapper 10, two PRG banks (at `$8000`
+    /// and `$C000`) and a CHR bank.
+    const HEADER: [u8; 16] = *b"NES\x1A\x02\x01\xA0\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+    /// A ROM whose PRG has `patches` written at their offset, the vectors
+    /// pointing to `$C000` unless patched.
+    fn rom(patches: &[(usize, &[u8])]) -> Vec<u8> {
+        let mut prg = vec![0u8; 2 * BANK_SIZE];
+        prg[0x7FFA..].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+        for (offset, bytes) in patches {
+            prg[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+        }
+        [&HEADER[..], &prg, &[0u8; CHR_SIZE]].concat()
+    }
+
+    /// A CDL of the ROM with `flags` set on the ranges of PRG offsets.
+    fn cdl(flags: &[(Range<usize>, u8)]) -> Vec<u8> {
+        let mut cdl = vec![0u8; 2 * BANK_SIZE + CHR_SIZE];
+        for (range, flag) in flags {
+            cdl[range.clone()].fill(*flag);
+        }
+        cdl
+    }
+
+    /// The files of the disassembly, `main.s` included, by name.
+    fn disassemble_files(rom: &[u8], cdl: &[u8], options: &Options) -> BTreeMap<String, String> {
+        let files = archive::Files::default();
+        let mut main = files.create("main.s");
+        disassemble_to_writer(rom, cdl, options, &mut main, |name| {
+            Ok(Box::new(files.create(name)))
+        })
+        .unwrap();
+        files.texts().into_iter().collect()
+    }
+
+    /// The PRG banks as disassembled, before they are written.
+    fn prg_banks(rom: &[u8], cdl: &[u8], options: &Options) -> Vec<BankResult> {
+        let mut banks = vec![];
+        disassemble_with_hook(
+            rom,
+            cdl,
+            options,
+            &mut io::sink(),
+            |_| Ok(Box::new(io::sink())),
+            &mut |_, bank| banks.push(bank.clone()),
+        )
+        .unwrap();
+        banks
+    }
+
+    /// The lines of the last PRG bank, trimmed.
+    fn last_bank(rom: &[u8], cdl: &[u8], options: &Options) -> Vec<String> {
+        disassemble_files(rom, cdl, options)["bank001.asm"]
+            .lines()
+            .map(|line| line.trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn inline_table_after_jmp() {
+        // JMP $C005 and RTS, with a table between them logged as code but
+        // also as read indirectly
+        let rom = rom(&[(0x4000, &[0x4C, 0x05, 0xC0, 0xA9, 0x12, 0x60])]);
+        let cdl = cdl(&[(0x4000..0x4006, 0x01), (0x4003..0x4005, 0x21)]);
+
+        for cdl_format in [CdlFormat::Fceux, CdlFormat::Mesen] {
+            let lines = last_bank(&rom, &cdl, &Options::new().cdl_format(cdl_format));
+            let table = lines.iter().position(|line| line == ".db $A9").unwrap();
+            assert_eq!(lines[table + 1], ".db $12");
+            assert!(!lines.iter().any(|line| line.starts_with("LDA")));
+            assert!(lines.contains(&"RTS".to_string()));
+        }
+    }
+
+    #[test]
+    fn labels_csv() {
+        // the same ROM with a trainer, the banks swapped by `--bank-order`
+        let mut rom = rom(&[(0x4000, &[0x60])]);
+        rom[6] |= 0x04;
+        rom.splice(0x10..0x10, [0u8; TRAINER_SIZE]);
+        let cdl = cdl(&[(0x4000..0x4001, 0x01)]);
+        let options = Options::new().labels_csv(true).bank_order(vec![1, 0]);
+
+        let csv = &disassemble_files(&rom, &cdl, &options)["labels.csv"];
+        assert!(
+            csv.lines().any(|line| line == "$04210,$C000,0,L00C000"),
+            "{csv}"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_invalid_opcode() {
+        // $02 isn't a 65C02 opcode, nor an NMOS one without the illegals
+        let rom = rom(&[(0x4000, &[0x02, 0x60])]);
+        let cdl = cdl(&[(0x4000..0x4002, 0x01)]);
+        let options = Options::new().json(Some("bank.json".into()));
+
+        let json = &disassemble_files(&rom, &cdl, &options)["bank.json"];
+        let banks: serde_json::Value = serde_json::from_str(json).unwrap();
+        let line = &banks[1]["lines"][0];
+        assert_eq!(line["kind"], "code");
+        assert_eq!(line["mnemonic"], serde_json::Value::Null);
+        assert_eq!(banks[1]["lines"][1]["mnemonic"], "RTS");
+    }
+
+    #[test]
+    fn oam_dma_page() {
+        // LDA #page / STA $4014 / RTS
+        let dma = |page: u8| rom(&[(0x4000, &[0xA9, page, 0x8D, 0x14, 0x40, 0x60])]);
+        let cdl = cdl(&[(0x4000..0x4006, 0x01)]);
+        let options = Options::new().annotate_idioms(true);
+
+        let main = &disassemble_files(&dma(0x02), &cdl, &options)["main.s"];
+        assert!(main.contains(OAM_BUFFER), "{main}");
+        for page in [0x00, 0x01, 0x08] {
+            let main = &disassemble_files(&dma(page), &cdl, &options)["main.s"];
+            assert!(!main.contains(OAM_BUFFER), "{main}");
+        }
+    }
+
+    #[test]
+    fn targets_at_the_bank_boundaries() {
+        let options = Options::new();
+        let target = |id: u8, addr: u16, rom_data: RomData| {
+            let [lo, hi] = addr.to_le_bytes();
+            get_target(id, lo, hi, rom_data, DataLabels::default(), &options)
+        };
+        // MMC4, and UxROM unsupported but placed the same
+        for mapper in [10, 2] {
+            let rom_data = RomData {
+                banks_count: 4,
+                mapper,
+                bank_size: BANK_SIZE,
+                base: None,
+            };
+            assert_eq!(get_bank_offset(0, rom_data), 0x8000);
+            assert_eq!(get_bank_offset(2, rom_data), 0x8000);
+            assert_eq!(get_bank_offset(3, rom_data), 0xC000);
+
+            // from a swappable bank
+            assert_eq!(
+                target(1, 0x7FFF, rom_data),
+                ("$7FFF.w".into(), Some(0x7FFF))
+            );
+            assert_eq!(
+                target(1, 0x8000, rom_data),
+                ("L018000.w".into(), Some(0x18000))
+            );
+            assert_eq!(
+                target(1, 0xBFFF, rom_data),
+                ("L01BFFF.w".into(), Some(0x1BFFF))
+            );
+            assert_eq!(
+                target(1, 0xC000, rom_data),
+                ("L03C000.w".into(), Some(0x3C000))
+            );
+            assert_eq!(
+                target(1, 0xFFFF, rom_data),
+                ("L03FFFF.w".into(), Some(0x3FFFF))
+            );
+            // from the fixed bank, the swappable one being unknown
+            assert_eq!(target(3, 0x8000, rom_data), ("$8000.w".into(), None));
+            assert_eq!(target(3, 0xBFFF, rom_data), ("$BFFF.w".into(), None));
+            assert_eq!(
+                target(3, 0xC000, rom_data),
+                ("L03C000.w".into(), Some(0x3C000))
+            );
+            assert_eq!(
+                target(3, 0xFFFF, rom_data),
+                ("L03FFFF.w".into(), Some(0x3FFFF))
+            );
+        }
+
+        // a 32KB bank covers the whole memory
+        let rom_data = RomData {
+            banks_count: 1,
+            mapper: 0,
+            bank_size: 0x8000,
+            base: None,
+        };
+        assert_eq!(
+            target(0, 0x7FFF, rom_data),
+            ("$7FFF.w".into(), Some(0x7FFF))
+        );
+        assert_eq!(
+            target(0, 0x8000, rom_data),
+            ("L008000.w".into(), Some(0x8000))
+        );
+        assert_eq!(
+            target(0, 0xFFFF, rom_data),
+            ("L00FFFF.w".into(), Some(0xFFFF))
+        );
+    }
+
+    /// The length of the operand from the bits `aaabbbcc` of the opcode, the
+    /// way the 6502 decodes them.
+    fn encoded_operand_len(byte: u8) -> usize {
+        let (a, b, c) = (byte >> 5, (byte >> 2) & 7, byte & 3);
+        match (b, c) {
+            // JSR is absolute, BRK, RTI and RTS implied
+            (0, 0) if byte == 0x20 => 2,
+            (0, 0) if a < 4 => 0,
+            // #imm and (zp,X)
+            (0, _) => 1,
+            // zp
+            (1, _) => 1,
+            // #imm, or the implied and accumulator ones
+            (2, 1 | 3) => 1,
+            (2, _) => 0,
+            // abs and (abs)
+            (3, _) => 2,
+            // the branches, (zp),Y and (zp)
+            (4, _) => 1,
+            // zp,X and zp,Y
+            (5, _) => 1,
+            // abs,Y, or implied
+            (6, 1 | 3) => 2,
+            (6, _) => 0,
+            // abs,X, abs,Y and (abs,X)
+            _ => 2,
+        }
+    }
+
+    #[test]
+    fn opcode_sizes() {
+        let rom_data = RomData {
+            banks_count: 1,
+            mapper: 0,
+            bank_size: 0x8000,
+            base: None,
+        };
+        for cpu in [Cpu::Nmos6502, Cpu::Cmos65c02, Cpu::NmosIllegal] {
+            let options = Options::new().cpu(cpu);
+            for (byte, opcode) in cpu.opcodes().iter().enumerate() {
+                let Some(opcode) = opcode else {
+                    continue;
+                };
+                let len = opcode.addressing.operand_len();
+                assert_eq!(
+                    len,
+                    encoded_operand_len(byte as u8),
+                    "{cpu:?} ${byte:02X} {}",
+                    opcode.name
+                );
+                let (size, _, _) = write_addressing(
+                    &opcode.addressing,
+                    &[0x34, 0x12],
+                    0,
+                    0x8000,
+                    rom_data,
+                    DataLabels::default(),
+                    &options,
+                )
+                .unwrap();
+                assert_eq!(size, len, "{cpu:?} ${byte:02X} {}", opcode.name);
+            }
+        }
+    }
+
+    #[test]
+    fn interrupt_vectors() {
+        // RTI at $C010 for the NMI, RTS at $C000 for the reset and the IRQ
+        let rom = rom(&[
+            (0x4000, &[0x60]),
+            (0x4010, &[0x40]),
+            (0x7FFA, &[0x10, 0xC0]),
+        ]);
+        let cdl = cdl(&[(0x4000..0x4001, 0x01), (0x4010..0x4011, 0x01)]);
+        let options = Options::new();
+
+        let banks = prg_banks(&rom, &cdl, &options);
+        let vectors: Vec<_> = banks[1]
+            .lines
+            .iter()
+            .filter(|line| line.text.trim_start().starts_with(".dw"))
+            .map(|line| (line.position, line.text.as_str()))
+            .collect();
+        assert_eq!(
+            vectors,
+            [
+                (Some(0x1FFFA), "    .dw L01C010 ; NMI"),
+                (Some(0x1FFFC), "    .dw L01C000 ; RESET"),
+                (Some(0x1FFFE), "    .dw L01C000 ; IRQ"),
+            ]
+        );
+
+        // the fixed bank is placed at $C000, so they land at $FFFA
+        let files = disassemble_files(&rom, &cdl, &options);
+        assert!(files["main.s"].contains("SLOTSIZE $4000\n        SLOT 1 $C000"));
+        let bank = &files["bank001.asm"];
+        assert!(
+            bank.starts_with("    .BANK 2 SLOT 1\n    .ORG $0000\n"),
+            "{bank}"
+        );
+        assert!(bank.contains("L01C010:\n"), "{bank}");
+        assert!(
+            bank.contains("    .dw L01C000 ; IRQ\n\n    .ENDS"),
+            "{bank}"
+        );
+    }
+
+    #[test]
+    fn absolute_zero_page_address() {
+        // LDA $0012 in its 3-byte encoding
+        let bank = [0xAD, 0x12, 0x00];
+        let result = disassemble_bank(&bank, &[1, 1, 1], 0xC000, &Options::new()).unwrap();
+        assert_eq!(result.lines[0].text, "    LDA $0012.w");
+
+        // asm6 has no size suffix, the bytes are kept
+        let options = Options::new().assembler(Assembler::Asm6);
+        let result = disassemble_bank(&bank, &[1, 1, 1], 0xC000, &options).unwrap();
+        assert_eq!(result.lines[0].text, "    .db $AD, $12, $00 ; LDA $0012");
+    }
+
+    #[test]
+    fn branch_out_of_the_bank() {
+        // BNE +127 and BEQ -128 from the start of a 4-byte bank
+        let bank = [0xD0, 0x7F, 0xF0, 0x80];
+        let result = disassemble_bank(&bank, &[1; 4], 0xC000, &Options::new()).unwrap();
+        let texts: Vec<_> = result.lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts[0], "    BNE $C081 ; out of the bank");
+        assert_eq!(texts[1], "    BEQ $BF84 ; out of the bank");
+        assert!(!result.labels.contains(&0xC081) && !result.labels.contains(&0xBF84));
+    }
+
+    #[test]
+    fn trace_indirect_jump() {
+        // JMP ($C010) from the reset, $C010 pointing to an RTS at $C020
+        let pointer = rom(&[
+            (0x4000, &[0x6C, 0x10, 0xC0]),
+            (0x4010, &[0x20, 0xC0]),
+            (0x4020, &[0x60]),
+        ]);
+        let lines = last_bank(&pointer, &cdl(&[]), &Options::new().trace(true));
+        let target = lines.iter().position(|line| line == "L01C020:").unwrap();
+        assert_eq!(lines[target + 1], "RTS");
+
+        // the pointer at $C1FF takes its high byte from $C100, not $C200
+        let wrapped = rom(&[
+            (0x4000, &[0x6C, 0xFF, 0xC1]),
+            (0x4030, &[0x60]),
+            (0x4100, &[0xC0]),
+            (0x41FF, &[0x30]),
+            (0x4200, &[0xD0]),
+        ]);
+        let lines = last_bank(&wrapped, &cdl(&[]), &Options::new().trace(true));
+        let target = lines.iter().position(|line| line == "L01C030:").unwrap();
+        assert_eq!(lines[target + 1], "RTS");
+    }
+
+    #[test]
+    fn pointer_table() {
+        // four pointers to the RTS at $C010, $C011, $C012 and $C013
+        let table = [0x10, 0xC0, 0x11, 0xC0, 0x12, 0xC0, 0x13, 0xC0];
+        let rom = rom(&[(0x4000, &table), (0x4010, &[0x60, 0x60, 0x60, 0x60])]);
+        let cdl = cdl(&[(0x4000..0x4008, 0x02), (0x4010..0x4014, 0x01)]);
+        let lines = last_bank(&rom, &cdl, &Options::new().pointer_tables(true));
+
+        let start = lines.iter().position(|line| line == "L01C000:").unwrap();
+        assert_eq!(
+            lines[start + 1..start + 5],
+            [".dw L01C010", ".dw L01C011", ".dw L01C012", ".dw L01C013"]
+        );
+        for label in ["L01C010:", "L01C011:", "L01C012:", "L01C013:"] {
+            assert!(lines.iter().any(|line| line == label), "{label}");
+        }
+    }
+
+    #[test]
+    fn word_align() {
+        // a byte, then two pointers to the RTS at $C010 and $C011
+        let table = [0x05, 0x10, 0xC0, 0x11, 0xC0];
+        let rom = rom(&[(0x4000, &table), (0x4010, &[0x60, 0x60])]);
+        let cdl = cdl(&[(0x4000..0x4005, 0x02), (0x4010..0x4012, 0x01)]);
+
+        let options = Options::new().pointer_tables(true);
+        let lines = last_bank(&rom, &cdl, &options.clone().word_align(true));
+        let start = lines.iter().position(|line| line == "L01C000:").unwrap();
+        assert_eq!(
+            lines[start + 1..start + 4],
+            [".db $05", ".dw L01C010", ".dw L01C011"]
+        );
+
+        // without, the words are looked for from the start of the data only
+        let lines = last_bank(&rom, &cdl, &options);
+        assert!(!lines.iter().any(|line| line.starts_with(".dw L01C01")));
+    }
+
+    #[test]
+    fn unlogged_bank() {
+        // LDA #1 / RTS in the first bank, which the CDL doesn't cover
+        let rom = rom(&[(0, &[0xA9, 0x01, 0x60])]);
+        let cdl = cdl(&[(0x4000..0x4001, 0x01)]);
+
+        let banks = prg_banks(&rom, &cdl, &Options::new());
+        assert_eq!(
+            banks[0].lines[0].text,
+            "; WARNING: the CDL logged nothing in this bank, try --trace or --linear-unlogged-banks"
+        );
+        assert!(banks[0].lines.iter().all(|line| !line.text.contains("LDA")));
+        assert!(!banks[1].lines[0].text.contains("logged nothing"));
+
+        let banks = prg_banks(&rom, &cdl, &Options::new().linear_unlogged_banks(true));
+        assert_eq!(
+            banks[0].lines[0].text,
+            "; the CDL logged nothing in this bank, disassembled as code"
+        );
+        assert!(banks[0].lines.iter().any(|line| line.text == "    LDA #1"));
+    }
+
+    #[test]
+    fn asm6_output() {
+        // LDA $0012 / JSR $C007 / RTS, then ASL A / RTS at $C007
+        let rom = rom(&[(
+            0x4000,
+            &[0xAD, 0x12, 0x00, 0x20, 0x07, 0xC0, 0x60, 0x0A, 0x60],
+        )]);
+        let cdl = cdl(&[(0x4000..0x4009, 0x01)]);
+        let options = Options::new()
+            .assembler(Assembler::Asm6)
+            .incbin_threshold(Some(16));
+
+        let files = disassemble_files(&rom, &cdl, &options);
+        assert_eq!(
+            files["main.s"],
+            concat!(
+                "; TV system: NTSC\n",
+                "    .db \"NES\", $1A\n",
+                "    .db $02\n",
+                "    .db $01\n",
+                "    .db $A0, $00, $00, $00, $00, $00, $00, $00, $00, $00\n",
+                "\n",
+                "    .include \"bank000.asm\"\n",
+                "    .include \"bank001.asm\"\n",
+                "\n",
+                "    .incbin \"bank000.chr\"\n",
+            )
+        );
+        assert_eq!(
+            files["bank001.asm"],
+            concat!(
+                "    .base $C000\n",
+                "\n",
+                "; coverage: code=0% data=0% unknown=100%\n",
+                "\n",
+                "L01C000:\n",
+                "    .db $AD, $12, $00 ; LDA $0012\n",
+                "    JSR L01C007\n",
+                "    RTS \n",
+                "\n",
+                "L01C007:\n",
+                "    ASL A\n",
+                "    RTS \n",
+                "\n",
+                "; start of data\n",
+                "    .incbin \"bank001.blob00.bin\"\n",
+                "; end of data\n",
+                "; interrupt vectors\n",
+                "    .dw L01C000 ; NMI\n",
+                "    .dw L01C000 ; RESET\n",
+                "    .dw L01C000 ; IRQ\n",
+            )
+        );
+        assert_eq!(files["bank001.blob00.bin"].len(), BANK_SIZE - 9 - 6);
+    }
+
+    #[test]
+    fn accumulator_per_assembler() {
+        // ASL A / ROL A / LSR A / ROR A
+        let bank = [0x0A, 0x2A, 0x4A, 0x6A];
+        for (assembler, operand) in [(Assembler::Wla, ""), (Assembler::Asm6, "A")] {
+            assert_eq!(assembler.accumulator(), operand);
+            let options = Options::new().assembler(assembler);
+            let result = disassemble_bank(&bank, &[1; 4], 0xC000, &options).unwrap();
+            let texts: Vec<_> = result.lines.iter().map(|line| line.text.as_str()).collect();
+            assert_eq!(
+                texts,
+                ["ASL", "ROL", "LSR", "ROR"].map(|name| format!("    {name} {operand}"))
+            );
+        }
+    }
+
+    #[test]
+    fn fixed_bank_reading_the_swappable_bank() {
+        // LDA $8000 / RTS in the fixed bank
+        let rom = rom(&[(0x4000, &[0xAD, 0x00, 0x80, 0x60])]);
+        let cdl = cdl(&[(0x4000..0x4004, 0x01)]);
+        let banks = prg_banks(&rom, &cdl, &Options::new());
+        let texts: Vec<_> = banks[1]
+            .lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect();
+        assert_eq!(
+            texts[..2],
+            [
+                "; $8000 is in the swappable bank, which one is unknown",
+                "    LDA $8000.w"
+            ]
+        );
+        assert!(banks[1].labels.iter().all(|label| label & 0xFFFF != 0x8000));
+    }
+
+    /// The numbers of the `.BANK`s of all the files, and the `BANKSTOTAL`.
+    fn wla_banks(files: &BTreeMap<String, String>) -> (Vec<usize>, usize) {
+        let numbers = files
+            .values()
+            .flat_map(|text| text.lines())
+            .filter_map(|line| line.trim().strip_prefix(".BANK "))
+            .map(|line| line.split(' ').next().unwrap().parse().unwrap())
+            .collect();
+        let total = files["main.s"]
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("BANKSTOTAL "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        (numbers, total)
+    }
+
+    #[test]
+    fn wla_bank_numbers() {
+        // MMC3, with two CHR banks in 1KB windows
+        let mut rom = rom(&[]);
+        rom[5] = 2;
+        rom[6] = 0x40;
+        rom.extend([0; CHR_SIZE]);
+        for options in [Options::new(), Options::new().bank_order(vec![1, 0])] {
+            let (mut numbers, total) = wla_banks(&disassemble_files(&rom, &[], &options));
+            numbers.sort_unstable();
+            // the header, 2 PRG banks and 16 CHR windows, each once
+            assert_eq!(total, 19);
+            assert_eq!(numbers, (0..total).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn chr_window_sizes() {
+        for (mapper, size) in [
+            (0, 0x2000),
+            (1, 0x1000),
+            (2, 0x2000),
+            (4, 0x400),
+            (5, 0x400),
+            (9, 0x1000),
+            (10, 0x1000),
+        ] {
+            assert_eq!(chr_window_size(mapper), size, "mapper {mapper}");
+        }
+    }
+
+    #[test]
+    fn memory_map_matches_the_banks() {
+        let hex = |text: &str| usize::from_str_radix(text.trim_start_matches('$'), 16).unwrap();
+        let mut mmc3 = rom(&[]);
+        mmc3[6] = 0x40;
+        for (rom, options) in [
+            (rom(&[]), Options::new()),
+            (rom(&[]), Options::new().org_per_section(true)),
+            (mmc3, Options::new()),
+        ] {
+            let files = disassemble_files(&rom, &[], &options);
+            // the size of the slots, and of the banks in order
+            let (mut slots, mut banks) = (HashMap::new(), vec![]);
+            let mut size = 0;
+            for line in files["main.s"].lines().map(str::trim) {
+                let words: Vec<_> = line.split(' ').collect();
+                match words[..] {
+                    ["SLOTSIZE", value] | ["BANKSIZE", value] => size = hex(value),
+                    ["SLOT", slot, _] => _ = slots.insert(slot.parse::<u8>().unwrap(), size),
+                    ["BANKS", count] => banks.extend(vec![size; count.parse().unwrap()]),
+                    _ => {}
+                }
+            }
+
+            let headers: Vec<_> = files
+                .values()
+                .flat_map(|text| text.lines().map(str::trim))
+                .filter(|line| line.starts_with(".BANK "))
+                .collect();
+            assert_eq!(headers.len(), banks.len());
+            for line in headers {
+                let words: Vec<_> = line.split(' ').collect();
+                let (bank, slot) = (
+                    words[1].parse::<usize>().unwrap(),
+                    words[3].parse().unwrap(),
+                );
+                assert_eq!(banks[bank], slots[&slot], "{line}");
+            }
+        }
+    }
+
+    #[test]
+    fn over_declared_banks() {
+        // 4 PRG and 2 CHR banks declared, the file only having 2 and 1
+        let mut rom = rom(&[]);
+        rom[4] = 4;
+        rom[5] = 2;
+        let summary = disassemble_to_writer(&rom, &[], &Options::new(), &mut io::sink(), |_| {
+            Ok(Box::new(io::sink()))
+        })
+        .unwrap();
+        assert_eq!((summary.prg_banks, summary.chr_banks), (2, 1));
+
+        // a truncated last bank is left out
+        let summary = disassemble_to_writer(
+            &rom[..0x10 + BANK_SIZE + 100],
+            &[],
+            &Options::new(),
+            &mut io::sink(),
+            |_| Ok(Box::new(io::sink())),
+        )
+        .unwrap();
+        assert_eq!((summary.prg_banks, summary.chr_banks), (1, 0));
+    }
+
+    #[test]
+    fn no_prg_bank() {
+        let mut rom = rom(&[]);
+        rom[4] = 0;
+        let error = disassemble_to_writer(&rom, &[], &Options::new(), &mut io::sink(), |_| {
+            Ok(Box::new(io::sink()))
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<DisasmError>(),
+            Some(DisasmError::NoPrgBanks)
+        ));
+    }
+
+    #[test]
+    fn decode_jmp() {
+        let opcode = decode_opcode(0x4C).unwrap();
+        assert_eq!(opcode.name, "JMP");
+        assert_eq!(opcode.addressing, Addressing::Absolute);
+        assert!(decode_opcode(0x02).is_none());
+    }
+
+    #[test]
+    fn zero_page_suffix() {
+        // LDA $10 / LDA $10,X
+        let bank = [0xA5, 0x10, 0xB5, 0x10];
+        let result = disassemble_bank(&bank, &[1; 4], 0xC000, &Options::new()).unwrap();
+        let texts: Vec<_> = result.lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, ["    LDA $10.b", "    LDA $10.b,X"]);
+
+        let options = Options::new().explicit_size(false);
+        let result = disassemble_bank(&bank, &[1; 4], 0xC000, &options).unwrap();
+        assert_eq!(result.lines[0].text, "    LDA $10");
+    }
+
+    #[test]
+    fn data_after_rts() {
+        // RTS, two bytes of data, then LDA #0
+        let bank = [0x60, 0x12, 0x34, 0xA9, 0x00];
+        let result = disassemble_bank(&bank, &[1, 2, 2, 1, 1], 0xC000, &Options::new()).unwrap();
+        let texts: Vec<_> = result.lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "    RTS ",
+                "",
+                "; start of data",
+                "    .db $12",
+                "    .db $34",
+                "; end of data",
+                "    LDA #0"
+            ]
+        );
+        assert!(!result.labels.contains(&0xC001));
+        assert!(result.labels.contains(&0xC003));
+    }
+
+    #[test]
+    fn indent_with_tabs() {
+        // RTS, then two bytes of data
+        let rom = rom(&[(0x4000, &[0x60, 0x12, 0x34])]);
+        let cdl = cdl(&[(0x4000..0x4001, 0x01), (0x4001..0x4003, 0x02)]);
+        let options = Options::new().indent(Some("\t".into()));
+
+        let files = disassemble_files(&rom, &cdl, &options);
+        let bank = &files["bank001.asm"];
+        assert!(
+            bank.starts_with("\t.BANK 2 SLOT 1\n\t.ORG $0000\n"),
+            "{bank}"
+        );
+        assert!(bank.contains("\t.SECTION \"Bank1\""), "{bank}");
+        assert!(bank.contains("\t.db $12\n\t.db $34\n"), "{bank}");
+        let main = &files["main.s"];
+        assert!(main.contains("\t.MEMORYMAP\n\t\tDEFAULTSLOT"), "{main}");
+        assert!(main.contains("\t.db $02\n"), "{main}");
+        // only the labels and the comments are left at the start of the line
+        for line in [main, bank].into_iter().flat_map(|text| text.lines()) {
+            assert!(
+                line.is_empty() || line.starts_with(['\t', ';']) || line.ends_with(':'),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn operand_flagged_as_data() {
+        // LDA $0234 whose operand is flagged as data, then RTS
+        let bank = [0xAD, 0x34, 0x02, 0x60];
+        let cdl = [1, 2, 2, 1];
+        let texts = |options: &Options| -> Vec<String> {
+            let result = disassemble_bank(&bank, &cdl, 0xC000, options).unwrap();
+            result.lines.into_iter().map(|line| line.text).collect()
+        };
+        assert_eq!(
+            texts(&Options::new()),
+            [
+                "; WARNING: operand byte flagged as data",
+                "    LDA $0234.w",
+                "    RTS ",
+                ""
+            ]
+        );
+
+        // or written as bytes
+        assert_eq!(
+            texts(&Options::new().operand_data_as_db(true)),
+            [
+                "    .db $AD ; operand flagged as data",
+                "; start of data",
+                "    .db $34",
+                "    .db $02",
+                "; end of data",
+                "    RTS ",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn max_line_bytes_split_at_labels() {
+        // LDA $C014 / RTS, then 16 bytes of data at $C010
+        let data: Vec<u8> = (0..16).collect();
+        let rom = rom(&[(0x4000, &[0xAD, 0x14, 0xC0, 0x60]), (0x4010, &data)]);
+        let cdl = cdl(&[(0x4000..0x4004, 0x01), (0x4010..0x4020, 0x02)]);
+        let lines = last_bank(&rom, &cdl, &Options::new().max_line_bytes(Some(16)));
+        let start = lines
+            .iter()
+            .position(|line| line.starts_with(".db $00, $01"))
+            .unwrap();
+        assert_eq!(
+            lines[start..start + 3],
+            [
+                ".db $00, $01, $02, $03",
+                "L01C014:",
+                ".db $04, $05, $06, $07, $08, $09, $0A, $0B, $0C, $0D, $0E, $0F",
+            ]
+        );
+    }
+
+    #[test]
+    fn data_label_prefix() {
+        // LDA $C010 / JMP $C000, the data at $C010
+        let rom = rom(&[
+            (0x4000, &[0xAD, 0x10, 0xC0, 0x4C, 0x00, 0xC0]),
+            (0x4010, &[0x12]),
+        ]);
+        let cdl = cdl(&[(0x4000..0x4006, 0x01), (0x4010..0x4011, 0x02)]);
+        let options = Options::new().data_label_prefix(Some("D".into()));
+        let lines = last_bank(&rom, &cdl, &options);
+        assert!(lines.iter().any(|line| line == "LDA D01C010.w"));
+        assert!(lines.iter().any(|line| line == "D01C010:"));
+        assert!(lines.iter().any(|line| line == "JMP L01C000.w"));
+    }
+
+    #[test]
+    fn nes2_save_ram() {
+        // LDA $6000 / RTS in a battery game, with 8KB of PRG-NVRAM in NES 2.0
+        let mut rom = rom(&[(0x4000, &[0xAD, 0x00, 0x60, 0x60])]);
+        rom[6] = 0xA2;
+        rom[7] = 0x08;
+        rom[10] = 0x70;
+        let cdl = cdl(&[(0x4000..0x4004, 0x01)]);
+        let options = Options::new().wram_vars(true);
+        let main = &disassemble_files(&rom, &cdl, &options)["main.s"];
+        assert!(main.contains(".RAMSECTION \"SRAM\" SLOT"), "{main}");
+
+        // and none without PRG-RAM
+        rom[10] = 0x00;
+        let main = &disassemble_files(&rom, &cdl, &options)["main.s"];
+        assert!(!main.contains(".RAMSECTION \"SRAM\""), "{main}");
+    }
+}
@@ -1,1031 +1,454 @@
-use byteorder::BigEndian;
-use byteorder::ReadBytesExt;
+use clap::ArgAction;
 use clap::Parser;
-use std::collections::HashSet;
-use std::fs;
+use clap::builder::PossibleValuesParser;
+use clap::builder::TypedValueParser;
+use log::LevelFilter;
+use nes_disasm::Assembler;
+use nes_disasm::CdlFormat;
+use nes_disasm::Cpu;
+use nes_disasm::HexPrefix;
+use nes_disasm::Layout;
+use nes_disasm::Location;
+use nes_disasm::Options;
+use nes_disasm::Palette;
+use nes_disasm::SectionMode;
+use nes_disasm::compare;
+use nes_disasm::disassemble;
+use nes_disasm::read_header;
+#[cfg(feature = "serde")]
 use std::fs::File;
-use std::io::Error;
-use std::io::ErrorKind;
-use std::io::Read;
+#[cfg(feature = "serde")]
+use std::io;
+#[cfg(feature = "serde")]
 use std::io::Write;
 
 #[derive(Debug, Parser)]
 struct Args {
-    filename: String,
-
-    #[arg(short, long)]
-    cdl: String,
+    #[arg(required_unless_present = "compare")]
+    filename: Option<String>,
+
+    /// CDL file, can be repeated to merge several logs
+    #[arg(short, long, required_unless_present_any = ["header_only", "compare"])]
+    cdl: Vec<String>,
+
+    /// Emulator that wrote the CDL (fceux or mesen)
+    #[arg(long, default_value = "fceux")]
+    cdl_format: CdlFormat,
+
+    /// File of the ranges that are code or data whatever the CDL says
+    #[arg(long, value_name = "FILE")]
+    regions: Option<String>,
+
+    /// Output directory, or archive when it ends with .zip or .tar
+    #[cfg_attr(
+        feature = "tui",
+        arg(short, long, required_unless_present_any = ["header_only", "compare", "tui"])
+    )]
+    #[cfg_attr(
+        not(feature = "tui"),
+        arg(short, long, required_unless_present_any = ["header_only", "compare"])
+    )]
+    output: Option<String>,
+
+    /// Print the fields of the header, then exit
+    #[arg(long)]
+    header_only: bool,
+
+    /// Print the diff of two output directories, without the label addresses,
+    /// and exit with 1 if they differ
+    #[arg(long, num_args = 2, value_names = ["DIR1", "DIR2"])]
+    compare: Vec<String>,
+
+    /// Name the save RAM ($6000-$7FFF) addresses used as operands
+    #[arg(long)]
+    wram_vars: bool,
+
+    /// Follow the code reachable from the interrupt vectors
+    #[arg(long)]
+    trace: bool,
+
+    /// Trace the code from this entry point too, as $C123 or bank:offset (repeatable)
+    #[arg(long = "entry", value_name = "ADDR")]
+    entries: Vec<Location>,
+
+    /// While tracing, follow the tables of pointers used by JMP ($nnnn)
+    #[arg(long)]
+    follow_jmp_indirect_table: bool,
+
+    /// Write the control-flow graph of the routine at this address as a DOT file
+    #[arg(long, value_name = "ADDR")]
+    cfg: Option<Location>,
+
+    /// Name the zero-page pointers used by (zp),Y
+    #[arg(long)]
+    zp_pointers: bool,
+
+    /// Print what would be generated instead of writing it
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Comment the well-known sequences of instructions
+    #[arg(long)]
+    annotate_idioms: bool,
+
+    /// Disassemble as code the banks where the CDL logged nothing
+    #[arg(long)]
+    linear_unlogged_banks: bool,
+
+    /// Indent the instructions, data and directives with N spaces
+    #[arg(long, value_name = "N", conflicts_with = "indent_tabs")]
+    indent: Option<usize>,
+
+    /// Indent the instructions, data and directives with a tab
+    #[arg(long)]
+    indent_tabs: bool,
+
+    /// Name the illegal opcodes in a comment, without decoding them
+    #[arg(long)]
+    opcode_illegals_as_comment: bool,
+
+    /// Comment the data with the instructions reading it
+    #[arg(long)]
+    data_xref: bool,
+
+    /// Emit the tables of pointers into the ROM as words, labelled
+    #[arg(long)]
+    pointer_tables: bool,
+
+    /// Realign the pointer tables after every single byte of data
+    #[arg(long, requires = "pointer_tables")]
+    word_align: bool,
+
+    /// Fail instead of guessing when the ROM isn't fully supported
+    #[arg(long)]
+    strict: bool,
+
+    /// Print more information (-v for each step, -vv for the details)
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Only print the errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Name the immediate values and zero-page addresses used often
+    #[arg(long)]
+    define_constants: bool,
+
+    /// How many uses make a value a constant
+    #[arg(long, default_value_t = 8, requires = "define_constants")]
+    define_threshold: usize,
+
+    /// Emit as data the opcodes whose operand is flagged as data
+    #[arg(long)]
+    operand_data_as_db: bool,
+
+    /// Instructions to decode (nmos6502, 65c02 or nmos-illegal)
+    #[arg(long, default_value = "nmos6502")]
+    cpu: Cpu,
+
+    /// Assembler the output is written for (wla or asm6)
+    #[arg(long, default_value = "wla")]
+    assembler: Assembler,
+
+    /// Prefix of the hexadecimal numbers ($ or 0x)
+    #[arg(long, default_value = "$")]
+    hex_prefix: HexPrefix,
+
+    /// Write the hexadecimal numbers in lowercase
+    #[arg(long)]
+    lowercase_hex: bool,
+
+    /// Don't extract the CHR banks
+    #[arg(long)]
+    no_chr: bool,
+
+    /// Don't extract the CHR banks, but describe them in chr.txt
+    #[arg(long)]
+    prg_only: bool,
+
+    /// Size of the PRG banks in KB (8, 16 or 32)
+    #[arg(
+        long,
+        value_name = "KB",
+        value_parser = PossibleValuesParser::new(["8", "16", "32"]).map(|kb| kb.parse::<usize>().unwrap())
+    )]
+    bank_size: Option<usize>,
+
+    /// Number the PRG banks in this order, e.g. 2,0,1 for the bank 0 to become bank 2
+    #[arg(long, value_name = "IDS", value_delimiter = ',')]
+    bank_order: Vec<u8>,
+
+    /// First PRG bank to disassemble
+    #[arg(long, value_name = "N")]
+    start_bank: Option<u8>,
+
+    /// Last PRG bank to disassemble
+    #[arg(long, value_name = "M")]
+    end_bank: Option<u8>,
+
+    /// Write identical CHR banks to separate files
+    #[arg(long)]
+    no_dedupe: bool,
+
+    /// Write a Ghidra script creating the labels, in the output directory
+    #[arg(long, value_name = "SCRIPT")]
+    ghidra: Option<String>,
+
+    /// Write the address, opcode, mnemonic and addressing mode of every
+    /// instruction as TSV, in the output directory
+    #[arg(long, value_name = "FILE")]
+    tags: Option<String>,
+
+    /// List the files written, with their size, in manifest.txt
+    #[arg(long)]
+    manifest: bool,
+
+    /// Write the labels to labels.csv, in the output directory
+    #[arg(long)]
+    labels_csv: bool,
+
+    /// Trace the calls, jumps and branches of the logged code that the CDL missed
+    #[arg(long)]
+    follow_calls: bool,
+
+    /// Where the files go (flat, or nested in banks/ and chr/)
+    #[arg(long, default_value = "flat")]
+    layout: Layout,
+
+    /// Kind of the sections (force, free, semifree or superfree)
+    #[arg(long, default_value = "force")]
+    section_mode: SectionMode,
+
+    /// Name the sections PREFIX_header, PREFIX_bank0...
+    #[arg(long, value_name = "PREFIX")]
+    section_prefix: Option<String>,
+
+    /// Place the PRG banks at their CPU address ($8000 or $C000)
+    #[arg(long)]
+    org_per_section: bool,
+
+    /// Don't comment the bytes flagged as both code and data
+    #[arg(long)]
+    no_code_data_comments: bool,
+
+    /// Also write the banks sorted by CPU address to flat.asm
+    #[arg(long)]
+    flat: bool,
+
+    /// Write every routine to its own file
+    #[arg(long)]
+    split_routines: bool,
+
+    /// Include with .INCBIN the runs of at least N unknown bytes
+    #[arg(long, value_name = "N")]
+    incbin_threshold: Option<usize>,
+
+    /// Write up to N data bytes per .db line
+    #[arg(long, value_name = "N")]
+    max_line_bytes: Option<usize>,
+
+    /// Comment every instruction with its bytes
+    #[arg(long)]
+    raw_bytes: bool,
+
+    /// Comment the data with its hex dump, like a hex editor
+    #[arg(long)]
+    hexdump: bool,
+
+    /// Comment every branch with its signed offset
+    #[arg(long)]
+    branch_offsets: bool,
+
+    /// Comment every branch with its direction, backward or forward
+    #[arg(long)]
+    branch_direction: bool,
+
+    /// Name the labels of a bank after their CPU address inside of it
+    #[arg(long)]
+    local_bank_labels: bool,
+
+    /// Name the labels that aren't on code with this prefix instead of L
+    #[arg(long, value_name = "PREFIX", num_args = 0..=1, default_missing_value = "D")]
+    data_label_prefix: Option<String>,
+
+    /// Comment every label with its CPU address
+    #[arg(long)]
+    label_comments: bool,
+
+    /// Warn about the labels referenced but not defined
+    #[arg(long)]
+    verify: bool,
+
+    /// Suffix the zero-page operands with .b and the absolute ones with .w
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
+    explicit_size: bool,
+
+    /// Name the game from this list of PRG CRC32s, as CRC32,title[,hint]
+    #[arg(long, value_name = "FILE")]
+    rom_db: Option<String>,
+
+    /// Write this file instead of the generated .MEMORYMAP and .ROMBANKMAP
+    #[arg(long, value_name = "FILE")]
+    memmap: Option<String>,
+
+    /// Comment the bits of the values stored into the PPU and APU registers
+    #[arg(long)]
+    decode_regs: bool,
+
+    /// Also write the CHR banks as PNG files
+    #[arg(long)]
+    chr_png: bool,
+
+    /// Colors of the CHR PNG files, as RRGGBB,RRGGBB,RRGGBB,RRGGBB
+    #[arg(
+        long,
+        value_name = "COLORS",
+        default_value = "000000,555555,AAAAAA,FFFFFF"
+    )]
+    palette: Palette,
+
+    /// Write every CHR bank to a single PNG, in the output directory
+    #[arg(long, value_name = "PNG")]
+    chr_sheet: Option<String>,
+
+    /// Tiles per row of the CHR sheet
+    #[arg(long, default_value_t = 16, requires = "chr_sheet")]
+    tiles_per_row: usize,
+
+    /// Write the disassembly as JSON, in the output directory (with
+    /// --header-only, write the header to FILE, - being the standard output)
+    #[cfg(feature = "serde")]
+    #[arg(long, value_name = "FILE")]
+    json: Option<String>,
+
+    /// Browse the disassembly in the terminal instead of writing it
+    #[cfg(feature = "tui")]
+    #[arg(long, conflicts_with = "output")]
+    tui: bool,
+}
 
-    #[arg(short, long)]
-    output: String,
+impl From<&Args> for Options {
+    fn from(args: &Args) -> Self {
+        let options = Options::new()
+            .cdl_format(args.cdl_format)
+            .regions(args.regions.clone())
+            .wram_vars(args.wram_vars)
+            .trace(args.trace)
+            .entries(args.entries.clone())
+            .follow_jmp_indirect_table(args.follow_jmp_indirect_table)
+            .cfg(args.cfg)
+            .linear_unlogged_banks(args.linear_unlogged_banks)
+            .indent(if args.indent_tabs {
+                Some("\t".to_string())
+            } else {
+                args.indent.map(|n| " ".repeat(n))
+            })
+            .data_xref(args.data_xref)
+            .opcode_illegals_as_comment(args.opcode_illegals_as_comment)
+            .pointer_tables(args.pointer_tables)
+            .word_align(args.word_align)
+            .annotate_idioms(args.annotate_idioms)
+            .zp_pointers(args.zp_pointers)
+            .dry_run(args.dry_run)
+            .strict(args.strict)
+            .define_constants(args.define_constants.then_some(args.define_threshold))
+            .operand_data_as_db(args.operand_data_as_db)
+            .cpu(args.cpu)
+            .assembler(args.assembler)
+            .hex_prefix(args.hex_prefix)
+            .lowercase_hex(args.lowercase_hex)
+            .no_chr(args.no_chr)
+            .prg_only(args.prg_only)
+            .bank_size(args.bank_size.map(|kb| kb * 1024))
+            .bank_order(args.bank_order.clone())
+            .bank_range(args.start_bank, args.end_bank)
+            .no_dedupe(args.no_dedupe)
+            .ghidra(args.ghidra.clone())
+            .tags(args.tags.clone())
+            .labels_csv(args.labels_csv)
+            .manifest(args.manifest)
+            .follow_calls(args.follow_calls)
+            .section_mode(args.section_mode)
+            .layout(args.layout)
+            .section_prefix(args.section_prefix.clone())
+            .org_per_section(args.org_per_section)
+            .no_code_data_comments(args.no_code_data_comments)
+            .flat(args.flat)
+            .split_routines(args.split_routines)
+            .incbin_threshold(args.incbin_threshold)
+            .max_line_bytes(args.max_line_bytes)
+            .raw_bytes(args.raw_bytes)
+            .hexdump(args.hexdump)
+            .branch_offsets(args.branch_offsets)
+            .branch_direction(args.branch_direction)
+            .local_bank_labels(args.local_bank_labels)
+            .data_label_prefix(args.data_label_prefix.clone())
+            .label_comments(args.label_comments)
+            .verify(args.verify)
+            .explicit_size(args.explicit_size)
+            .memory_map(args.memmap.clone())
+            .rom_db(args.rom_db.clone())
+            .decode_regs(args.decode_regs)
+            .chr_png(args.chr_png)
+            .palette(args.palette)
+            .chr_sheet(args.chr_sheet.clone(), args.tiles_per_row);
+        #[cfg(feature = "serde")]
+        let options = options.json(args.json.clone());
+        options
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
-    disassemble(&args.filename, &args.cdl, &args.output)
-}
-
-const BANK_SIZE: usize = 0x4000;
-const CHR_SIZE: usize = 0x2000;
-
-#[derive(Copy, Clone)]
-struct RomData {
-    banks_count: u8,
-    mapper: u8,
-}
-
-fn disassemble(filename: &str, cdl: &str, output: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let data: Vec<u8> = fs::read(&cdl)?;
-
-    let mut rom = File::open(filename)?;
-
-    let ines = rom.read_u32::<BigEndian>()?;
-    if ines != 0x4E45531A {
-        return Err(Box::new(Error::new(
-            ErrorKind::InvalidInput,
-            "This file is not an iNES ROM.",
-        )));
-    }
-
-    let prg_banks_count = rom.read_u8()?;
-    let chr_banks_count = rom.read_u8()?;
-    let flags_06 = rom.read_u8()?;
-    let mut padding = vec![0u8; 9];
-    rom.read(&mut padding)?;
-    let mapper = flags_06 >> 4;
-
-    fs::create_dir_all(output)?;
-    let mut output_file = File::create(format!("{output}/main.s"))?;
-
-    writeln!(output_file, ".MEMORYMAP")?;
-    writeln!(output_file, "    DEFAULTSLOT 1")?;
-    writeln!(output_file, "    SLOTSIZE $0010")?;
-    writeln!(output_file, "    SLOT 0 $0000")?;
-    writeln!(output_file, "    SLOTSIZE ${BANK_SIZE:X}")?;
-    writeln!(output_file, "    SLOT 1 $C000")?;
-    writeln!(output_file, "    SLOTSIZE ${CHR_SIZE:X}")?;
-    writeln!(output_file, "    SLOT 2 $0000")?;
-    writeln!(output_file, "    SLOTSIZE $800")?;
-    writeln!(output_file, "    SLOT 3 $0000")?;
-    writeln!(output_file, ".ENDME\n")?;
-
-    writeln!(output_file, ".ROMBANKMAP")?;
-    writeln!(
-        output_file,
-        "    BANKSTOTAL {}",
-        prg_banks_count + chr_banks_count + 1
-    )?;
-    writeln!(output_file, "    BANKSIZE $0010")?;
-    writeln!(output_file, "    BANKS 1")?;
-    writeln!(output_file, "    BANKSIZE ${BANK_SIZE:X}")?;
-    writeln!(output_file, "    BANKS {prg_banks_count}")?;
-    writeln!(output_file, "    BANKSIZE ${CHR_SIZE:X}")?;
-    writeln!(output_file, "    BANKS {chr_banks_count}")?;
-    writeln!(output_file, ".ENDRO\n")?;
-
-    writeln!(output_file, ".BANK 0 SLOT 0")?;
-    writeln!(output_file, ".ORG $0000\n")?;
-    writeln!(output_file, ".SECTION \"Header\" FORCE\n")?;
-    writeln!(output_file, ".db \"NES\", $1A")?;
-    writeln!(output_file, ".db ${prg_banks_count:02X}")?;
-    writeln!(output_file, ".db ${chr_banks_count:02X}")?;
-    write!(output_file, ".db ${flags_06:02X}")?;
-    for b in padding {
-        write!(output_file, " ${b:02X}")?;
-    }
-    writeln!(output_file, "\n\n.ENDS\n")?;
-
-    writeln!(output_file, ".RAMSECTION \"RAM\" SLOT 3")?;
-    writeln!(output_file, ".ENDS\n")?;
-
-    let rom_data = RomData {
-        banks_count: prg_banks_count,
-        mapper,
+    let level = match args.verbose {
+        _ if args.quiet => LevelFilter::Error,
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        _ => LevelFilter::Debug,
     };
-    for id in 0..prg_banks_count {
-        writeln!(output_file, ".INCLUDE \"bank{id:03}.asm\"")?;
-
-        let mut bank = vec![0u8; BANK_SIZE];
-        rom.read(&mut bank)?;
-
-        let bank_offset = (id as usize) * BANK_SIZE;
-        let cld_part = &data[bank_offset..bank_offset + BANK_SIZE];
-        assert_eq!(cld_part.len(), BANK_SIZE);
-
-        disassemble_prg_bank(id, bank, rom_data, cld_part, output)?;
-    }
-
-    for id in 0..chr_banks_count {
-        writeln!(output_file, "\n.BANK {} SLOT 2", id + prg_banks_count + 1)?;
-        writeln!(output_file, ".ORG $0000")?;
-        writeln!(output_file, ".INCBIN \"bank{id:03}.chr\"")?;
-
-        let mut bank = vec![0u8; CHR_SIZE];
-        rom.read(&mut bank)?;
-        fs::write(format!("{output}/bank{id:03}.chr"), bank)?;
-    }
-
-    Ok(())
-}
-
-fn disassemble_prg_bank(
-    id: u8,
-    bank: Vec<u8>,
-    rom_data: RomData,
-    cdl: &[u8],
-    path: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut buffer = vec![];
-
-    let mut i = 0;
-    let mut print_label = true;
-    let mut labels = HashSet::new();
-    let mut is_inside_data = false;
-
-    let bank_offset = get_bank_offset(id, rom_data.banks_count, rom_data.mapper);
-    while i < bank.len() {
-        let g_offset = i + id as usize * 0x10000 + bank_offset;
-
-        if (cdl[i] & 1) == 1 {
-            // is code
-            if is_inside_data {
-                buffer.push((0, format!("; end of data")));
-                is_inside_data = false;
-            }
-
-            // if (cdl[i] & 3) == 3 {
-            // buffer.push((0, format!("; code AND data???")));
-            // }
-
-            let op = bank[i] as usize;
-            if let Some(Some(opcode)) = OPCODES.get(op) {
-                if print_label {
-                    labels.insert(g_offset);
-                    print_label = false;
-                }
-
-                let (size, output, target) =
-                    write_addressing(&opcode.addressing, &bank[(i + 1)..], id, g_offset, rom_data)?;
-                i += size;
-
-                if let Some(addr) = target {
-                    labels.insert(addr);
-                }
-
-                buffer.push((g_offset, format!("    {} {}", opcode.name, output)));
-
-                if opcode.name == "RTS" || opcode.name == "JMP" {
-                    buffer.push((0, "".into()));
-                    print_label = true;
-                }
-            } else {
-                buffer.push((g_offset, format!(".db ${op:02X} ; invalid opcode?")));
-            }
-        } else if (cdl[i] & 3) == 2 {
-            // is data
-            if !is_inside_data {
-                buffer.push((0, format!("; start of data")));
-                is_inside_data = true;
-            }
-
-            buffer.push((g_offset, format!(".db ${:02X}", bank[i])));
-        } else {
-            // is unknown
-            if is_inside_data {
-                buffer.push((0, format!("; end of data")));
-                is_inside_data = false;
-            }
-
-            print_label = true;
-            buffer.push((g_offset, format!(".db ${:02X}", bank[i])));
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .init();
+    let options = Options::from(&args);
+
+    if let [first, second] = args.compare.as_slice() {
+        // like diff, exits with 1 when they differ
+        if compare(first, second, &mut std::io::stdout().lock())? {
+            std::process::exit(1);
         }
-
-        i += 1;
+        return Ok(());
     }
 
-    if is_inside_data {
-        buffer.push((0, format!("; end of data")));
+    let filename = args.filename.as_deref().unwrap_or_default();
+    #[cfg(feature = "tui")]
+    if args.tui {
+        return nes_disasm::browse(filename, &args.cdl, &options);
     }
 
-    let mut output = File::create(format!("{path}/bank{id:03}.asm"))?;
-
-    writeln!(output, ".BANK {}", id + 1)?;
-    writeln!(output, ".ORG $0000\n")?;
-    writeln!(output, ".SECTION \"Bank{id}\" FORCE\n")?;
-
-    for (addr, s) in buffer {
-        if labels.contains(&addr) {
-            writeln!(output, "L{addr:06X}:")?;
+    if args.header_only {
+        let header = read_header(filename)?;
+        #[cfg(feature = "serde")]
+        if let Some(path) = &args.json {
+            let mut output: Box<dyn Write> = match path.as_str() {
+                "-" => Box::new(io::stdout()),
+                path => Box::new(File::create(path)?),
+            };
+            serde_json::to_writer_pretty(&mut output, &header)?;
+            writeln!(output)?;
+            return Ok(());
         }
-        writeln!(output, "{s}")?;
+        println!("{header}");
+        return Ok(());
     }
 
-    writeln!(output, "\n.ENDS")?;
-
-    Ok(())
-}
-
-fn get_bank_offset(bank: u8, banks_count: u8, mapper: u8) -> usize {
-    match mapper {
-        10 => {
-            if bank == banks_count - 1 {
-                0xC000
-            } else {
-                0x8000
-            }
-        }
-        _ => {
-            println!("Unhandled mapper: {mapper}");
-            0x8000
-        }
+    let output = args.output.as_deref().unwrap_or_default();
+    let summary = disassemble(filename, &args.cdl, output, &options)?;
+    if args.dry_run {
+        println!("{summary}");
     }
-}
 
-fn write_addressing(
-    addressing: &Addressing,
-    bank: &[u8],
-    id: u8,
-    position: usize,
-    rom_data: RomData,
-) -> Result<(usize, String, Option<usize>), Box<dyn std::error::Error>> {
-    Ok(match addressing {
-        Addressing::Absolute => {
-            let (label, target) = get_target(id, bank[0], bank[1], rom_data);
-            (2, label, Some(target))
-        }
-        Addressing::AbsoluteX => {
-            let (label, target) = get_target(id, bank[0], bank[1], rom_data);
-            (2, format!("{label},X"), Some(target))
-        }
-        Addressing::AbsoluteY => {
-            let (label, target) = get_target(id, bank[0], bank[1], rom_data);
-            (2, format!("{label},Y"), Some(target))
-        }
-        Addressing::Accumulator => (0, "".into(), None),
-        Addressing::Immediate => (1, format!("#{}", bank[0]), None),
-        Addressing::Implied => (0, "".into(), None),
-        Addressing::Indirect => (2, format!("(${:02X}{:02X})", bank[1], bank[0]), None),
-        Addressing::IndirectY => (1, format!("(${:02X}),Y", bank[0]), None),
-        Addressing::Relative => {
-            let offset = bank[0] as i8 as isize;
-            let position = position as isize + offset + 2;
-            (1, format!("L{:06X}", position), Some(position as usize))
-        }
-        Addressing::XIndirect => (1, format!("(${:02X},X)", bank[0]), None),
-        Addressing::ZeroPage => (1, format!("${:02X}", bank[0]), None),
-        Addressing::ZeroPageX => (1, format!("${:02X},X", bank[0]), None),
-        Addressing::ZeroPageY => (1, format!("${:02X},Y", bank[0]), None),
-    })
-}
-
-fn get_target(id: u8, lo: u8, hi: u8, rom_data: RomData) -> (String, usize) {
-    let addr = ((hi as usize) << 8) + (lo as usize);
-
-    // check if RAM address
-    if addr < 0x0800 || (addr >= 0x6000 && addr < 0x8000) {
-        return (format!("${addr:04X}"), addr);
-    }
-
-    // MMC4 = last bank is fixed at $C000-FFFF
-    let target = if addr >= 0xC000 {
-        ((rom_data.banks_count - 1) as usize) << 16
-    } else {
-        (id as usize) << 16
-    } + addr;
-
-    (format!("L{target:06X}.w"), target)
-}
-
-enum Addressing {
-    Absolute,
-    AbsoluteX,
-    AbsoluteY,
-    Accumulator,
-    Immediate,
-    Implied,
-    Indirect,
-    IndirectY,
-    Relative,
-    XIndirect,
-    ZeroPage,
-    ZeroPageX,
-    ZeroPageY,
-}
-
-struct Opcode {
-    name: &'static str,
-    addressing: Addressing,
+    Ok(())
 }
-
-const OPCODES: [Option<Opcode>; 256] = [
-    Some(Opcode {
-        name: "BRK",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "ORA",
-        addressing: Addressing::XIndirect,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "ORA",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "ASL",
-        addressing: Addressing::ZeroPage,
-    }),
-    None,
-    Some(Opcode {
-        name: "PHP",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "ORA",
-        addressing: Addressing::Immediate,
-    }),
-    Some(Opcode {
-        name: "ASL",
-        addressing: Addressing::Accumulator,
-    }),
-    None,
-    None,
-    Some(Opcode {
-        name: "ORA",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "ASL",
-        addressing: Addressing::Absolute,
-    }),
-    None,
-    Some(Opcode {
-        name: "BPL",
-        addressing: Addressing::Relative,
-    }),
-    Some(Opcode {
-        name: "ORA",
-        addressing: Addressing::IndirectY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "ORA",
-        addressing: Addressing::ZeroPageX,
-    }),
-    Some(Opcode {
-        name: "ASL",
-        addressing: Addressing::ZeroPageX,
-    }),
-    None,
-    Some(Opcode {
-        name: "CLC",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "ORA",
-        addressing: Addressing::AbsoluteY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "ORA",
-        addressing: Addressing::AbsoluteX,
-    }),
-    Some(Opcode {
-        name: "ASL",
-        addressing: Addressing::AbsoluteX,
-    }),
-    None,
-    Some(Opcode {
-        name: "JSR",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "AND",
-        addressing: Addressing::XIndirect,
-    }),
-    None,
-    None,
-    Some(Opcode {
-        name: "BIT",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "AND",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "ROL",
-        addressing: Addressing::ZeroPage,
-    }),
-    None,
-    Some(Opcode {
-        name: "PLP",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "AND",
-        addressing: Addressing::Immediate,
-    }),
-    Some(Opcode {
-        name: "ROL",
-        addressing: Addressing::Accumulator,
-    }),
-    None,
-    Some(Opcode {
-        name: "BIT",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "AND",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "ROL",
-        addressing: Addressing::Absolute,
-    }),
-    None,
-    Some(Opcode {
-        name: "BMI",
-        addressing: Addressing::Relative,
-    }),
-    Some(Opcode {
-        name: "AND",
-        addressing: Addressing::IndirectY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "AND",
-        addressing: Addressing::ZeroPageX,
-    }),
-    Some(Opcode {
-        name: "ROL",
-        addressing: Addressing::ZeroPageX,
-    }),
-    None,
-    Some(Opcode {
-        name: "SEC",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "AND",
-        addressing: Addressing::AbsoluteY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "AND",
-        addressing: Addressing::AbsoluteX,
-    }),
-    Some(Opcode {
-        name: "ROL",
-        addressing: Addressing::AbsoluteX,
-    }),
-    None,
-    Some(Opcode {
-        name: "RTI",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "EOR",
-        addressing: Addressing::XIndirect,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "EOR",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "LSR",
-        addressing: Addressing::ZeroPage,
-    }),
-    None,
-    Some(Opcode {
-        name: "PHA",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "EOR",
-        addressing: Addressing::Immediate,
-    }),
-    Some(Opcode {
-        name: "LSR",
-        addressing: Addressing::Accumulator,
-    }),
-    None,
-    Some(Opcode {
-        name: "JMP",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "EOR",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "LSR",
-        addressing: Addressing::Absolute,
-    }),
-    None,
-    Some(Opcode {
-        name: "BVC",
-        addressing: Addressing::Relative,
-    }),
-    Some(Opcode {
-        name: "EOR",
-        addressing: Addressing::IndirectY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "EOR",
-        addressing: Addressing::ZeroPageX,
-    }),
-    Some(Opcode {
-        name: "LSR",
-        addressing: Addressing::ZeroPageX,
-    }),
-    None,
-    Some(Opcode {
-        name: "CLI",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "EOR",
-        addressing: Addressing::AbsoluteY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "EOR",
-        addressing: Addressing::AbsoluteX,
-    }),
-    Some(Opcode {
-        name: "LSR",
-        addressing: Addressing::AbsoluteX,
-    }),
-    None,
-    Some(Opcode {
-        name: "RTS",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "ADC",
-        addressing: Addressing::XIndirect,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "ADC",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "ROR",
-        addressing: Addressing::ZeroPage,
-    }),
-    None,
-    Some(Opcode {
-        name: "PLA",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "ADC",
-        addressing: Addressing::Immediate,
-    }),
-    Some(Opcode {
-        name: "ROR",
-        addressing: Addressing::Accumulator,
-    }),
-    None,
-    Some(Opcode {
-        name: "JMP",
-        addressing: Addressing::Indirect,
-    }),
-    Some(Opcode {
-        name: "ADC",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "ROR",
-        addressing: Addressing::Absolute,
-    }),
-    None,
-    Some(Opcode {
-        name: "BVS",
-        addressing: Addressing::Relative,
-    }),
-    Some(Opcode {
-        name: "ADC",
-        addressing: Addressing::IndirectY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "ADC",
-        addressing: Addressing::ZeroPageX,
-    }),
-    Some(Opcode {
-        name: "ROR",
-        addressing: Addressing::ZeroPageX,
-    }),
-    None,
-    Some(Opcode {
-        name: "SEI",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "ADC",
-        addressing: Addressing::AbsoluteY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "ADC",
-        addressing: Addressing::AbsoluteX,
-    }),
-    Some(Opcode {
-        name: "ROR",
-        addressing: Addressing::AbsoluteX,
-    }),
-    None,
-    None,
-    Some(Opcode {
-        name: "STA",
-        addressing: Addressing::XIndirect,
-    }),
-    None,
-    None,
-    Some(Opcode {
-        name: "STY",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "STA",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "STX",
-        addressing: Addressing::ZeroPage,
-    }),
-    None,
-    Some(Opcode {
-        name: "DEY",
-        addressing: Addressing::Implied,
-    }),
-    None,
-    Some(Opcode {
-        name: "TXA",
-        addressing: Addressing::Implied,
-    }),
-    None,
-    Some(Opcode {
-        name: "STY",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "STA",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "STX",
-        addressing: Addressing::Absolute,
-    }),
-    None,
-    Some(Opcode {
-        name: "BCC",
-        addressing: Addressing::Relative,
-    }),
-    Some(Opcode {
-        name: "STA",
-        addressing: Addressing::IndirectY,
-    }),
-    None,
-    None,
-    Some(Opcode {
-        name: "STY",
-        addressing: Addressing::ZeroPageX,
-    }),
-    Some(Opcode {
-        name: "STA",
-        addressing: Addressing::ZeroPageX,
-    }),
-    Some(Opcode {
-        name: "STX",
-        addressing: Addressing::ZeroPageY,
-    }),
-    None,
-    Some(Opcode {
-        name: "TYA",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "STA",
-        addressing: Addressing::AbsoluteY,
-    }),
-    Some(Opcode {
-        name: "TXS",
-        addressing: Addressing::Implied,
-    }),
-    None,
-    None,
-    Some(Opcode {
-        name: "STA",
-        addressing: Addressing::AbsoluteX,
-    }),
-    None,
-    None,
-    Some(Opcode {
-        name: "LDY",
-        addressing: Addressing::Immediate,
-    }),
-    Some(Opcode {
-        name: "LDA",
-        addressing: Addressing::XIndirect,
-    }),
-    Some(Opcode {
-        name: "LDX",
-        addressing: Addressing::Immediate,
-    }),
-    None,
-    Some(Opcode {
-        name: "LDY",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "LDA",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "LDX",
-        addressing: Addressing::ZeroPage,
-    }),
-    None,
-    Some(Opcode {
-        name: "TAY",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "LDA",
-        addressing: Addressing::Immediate,
-    }),
-    Some(Opcode {
-        name: "TAX",
-        addressing: Addressing::Implied,
-    }),
-    None,
-    Some(Opcode {
-        name: "LDY",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "LDA",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "LDX",
-        addressing: Addressing::Absolute,
-    }),
-    None,
-    Some(Opcode {
-        name: "BCS",
-        addressing: Addressing::Relative,
-    }),
-    Some(Opcode {
-        name: "LDA",
-        addressing: Addressing::IndirectY,
-    }),
-    None,
-    None,
-    Some(Opcode {
-        name: "LDY",
-        addressing: Addressing::ZeroPageX,
-    }),
-    Some(Opcode {
-        name: "LDA",
-        addressing: Addressing::ZeroPageX,
-    }),
-    Some(Opcode {
-        name: "LDX",
-        addressing: Addressing::ZeroPageY,
-    }),
-    None,
-    Some(Opcode {
-        name: "CLV",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "LDA",
-        addressing: Addressing::AbsoluteY,
-    }),
-    Some(Opcode {
-        name: "TSX",
-        addressing: Addressing::Implied,
-    }),
-    None,
-    Some(Opcode {
-        name: "LDY",
-        addressing: Addressing::AbsoluteX,
-    }),
-    Some(Opcode {
-        name: "LDA",
-        addressing: Addressing::AbsoluteX,
-    }),
-    Some(Opcode {
-        name: "LDX",
-        addressing: Addressing::AbsoluteY,
-    }),
-    None,
-    Some(Opcode {
-        name: "CPY",
-        addressing: Addressing::Immediate,
-    }),
-    Some(Opcode {
-        name: "CMP",
-        addressing: Addressing::XIndirect,
-    }),
-    None,
-    None,
-    Some(Opcode {
-        name: "CPY",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "CMP",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "DEC",
-        addressing: Addressing::ZeroPage,
-    }),
-    None,
-    Some(Opcode {
-        name: "INY",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "CMP",
-        addressing: Addressing::Immediate,
-    }),
-    Some(Opcode {
-        name: "DEX",
-        addressing: Addressing::Implied,
-    }),
-    None,
-    Some(Opcode {
-        name: "CPY",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "CMP",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "DEC",
-        addressing: Addressing::Absolute,
-    }),
-    None,
-    Some(Opcode {
-        name: "BNE",
-        addressing: Addressing::Relative,
-    }),
-    Some(Opcode {
-        name: "CMP",
-        addressing: Addressing::IndirectY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "CMP",
-        addressing: Addressing::ZeroPageX,
-    }),
-    Some(Opcode {
-        name: "DEC",
-        addressing: Addressing::ZeroPageX,
-    }),
-    None,
-    Some(Opcode {
-        name: "CLD",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "CMP",
-        addressing: Addressing::AbsoluteY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "CMP",
-        addressing: Addressing::AbsoluteX,
-    }),
-    Some(Opcode {
-        name: "DEC",
-        addressing: Addressing::AbsoluteX,
-    }),
-    None,
-    Some(Opcode {
-        name: "CPX",
-        addressing: Addressing::Immediate,
-    }),
-    Some(Opcode {
-        name: "SBC",
-        addressing: Addressing::XIndirect,
-    }),
-    None,
-    None,
-    Some(Opcode {
-        name: "CPX",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "SBC",
-        addressing: Addressing::ZeroPage,
-    }),
-    Some(Opcode {
-        name: "INC",
-        addressing: Addressing::ZeroPage,
-    }),
-    None,
-    Some(Opcode {
-        name: "INX",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "SBC",
-        addressing: Addressing::Immediate,
-    }),
-    Some(Opcode {
-        name: "NOP",
-        addressing: Addressing::Implied,
-    }),
-    None,
-    Some(Opcode {
-        name: "CPX",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "SBC",
-        addressing: Addressing::Absolute,
-    }),
-    Some(Opcode {
-        name: "INC",
-        addressing: Addressing::Absolute,
-    }),
-    None,
-    Some(Opcode {
-        name: "BEQ",
-        addressing: Addressing::Relative,
-    }),
-    Some(Opcode {
-        name: "SBC",
-        addressing: Addressing::IndirectY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "SBC",
-        addressing: Addressing::ZeroPageX,
-    }),
-    Some(Opcode {
-        name: "INC",
-        addressing: Addressing::ZeroPageX,
-    }),
-    None,
-    Some(Opcode {
-        name: "SED",
-        addressing: Addressing::Implied,
-    }),
-    Some(Opcode {
-        name: "SBC",
-        addressing: Addressing::AbsoluteY,
-    }),
-    None,
-    None,
-    None,
-    Some(Opcode {
-        name: "SBC",
-        addressing: Addressing::AbsoluteX,
-    }),
-    Some(Opcode {
-        name: "INC",
-        addressing: Addressing::AbsoluteX,
-    }),
-    None,
-];
@@ -0,0 +1,945 @@
+use crate::CMOS_OPCODES;
+use crate::DataLabels;
+use crate::ILLEGAL_OPCODES;
+use crate::OPCODES;
+use crate::Opcode;
+use std::fmt;
+use std::str::FromStr;
+
+/// Settings of a disassembly. Built with chained setters, starting from
+/// `Options::new()` which reproduces the default output:
+///
+/// ```
+/// let options = nes_disasm::Options::new().wram_vars(true);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub(crate) wram_vars: bool,
+    pub(crate) trace: bool,
+    pub(crate) cfg: Option<Location>,
+    pub(crate) pointer_tables: bool,
+    pub(crate) strict: bool,
+    pub(crate) define_constants: Option<usize>,
+    pub(crate) operand_data_as_db: bool,
+    pub(crate) hex_prefix: HexPrefix,
+    pub(crate) lowercase_hex: bool,
+    pub(crate) no_chr: bool,
+    pub(crate) start_bank: Option<u8>,
+    pub(crate) end_bank: Option<u8>,
+    pub(crate) no_dedupe: bool,
+    pub(crate) ghidra: Option<String>,
+    pub(crate) tags: Option<String>,
+    pub(crate) labels_csv: bool,
+    pub(crate) follow_calls: bool,
+    pub(crate) org_per_section: bool,
+    pub(crate) no_code_data_comments: bool,
+    pub(crate) split_routines: bool,
+    pub(crate) raw_bytes: bool,
+    pub(crate) hexdump: bool,
+    pub(crate) label_comments: bool,
+    pub(crate) entries: Vec<Location>,
+    pub(crate) verify: bool,
+    /// inverted so that the suffixes are written by default
+    pub(crate) implicit_size: bool,
+    pub(crate) memory_map: Option<String>,
+    pub(crate) decode_regs: bool,
+    pub(crate) chr_png: bool,
+    pub(crate) palette: Palette,
+    pub(crate) chr_sheet: Option<String>,
+    pub(crate) tiles_per_row: usize,
+    pub(crate) zp_pointers: bool,
+    pub(crate) dry_run: bool,
+    pub(crate) cdl_format: CdlFormat,
+    pub(crate) section_mode: SectionMode,
+    pub(crate) layout: Layout,
+    pub(crate) annotate_idioms: bool,
+    pub(crate) section_prefix: Option<String>,
+    pub(crate) manifest: bool,
+    pub(crate) follow_jmp_indirect_table: bool,
+    pub(crate) incbin_threshold: Option<usize>,
+    pub(crate) regions: Option<String>,
+    pub(crate) branch_offsets: bool,
+    pub(crate) branch_direction: bool,
+    pub(crate) assembler: Assembler,
+    pub(crate) local_bank_labels: bool,
+    pub(crate) prg_only: bool,
+    pub(crate) max_line_bytes: Option<usize>,
+    pub(crate) rom_db: Option<String>,
+    pub(crate) data_label_prefix: Option<String>,
+    pub(crate) flat: bool,
+    pub(crate) cpu: Cpu,
+    pub(crate) bank_size: Option<usize>,
+    pub(crate) bank_order: Vec<u8>,
+    pub(crate) word_align: bool,
+    pub(crate) linear_unlogged_banks: bool,
+    pub(crate) opcode_illegals_as_comment: bool,
+    pub(crate) indent: Option<String>,
+    pub(crate) data_xref: bool,
+    #[cfg(feature = "serde")]
+    pub(crate) json: Option<String>,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names the `$6000-$7FFF` addresses used as operands (`sram_XXXX`).
+    pub fn wram_vars(mut self, wram_vars: bool) -> Self {
+        self.wram_vars = wram_vars;
+        self
+    }
+
+    /// Follows the code reachable from the interrupt vectors to find what
+    /// the CDL missed.
+    pub fn trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Traces the targets of the calls, jumps and branches of the code
+    /// flagged by the CDL, when they aren't flagged themselves.
+    pub fn follow_calls(mut self, follow_calls: bool) -> Self {
+        self.follow_calls = follow_calls;
+        self
+    }
+
+    /// While tracing, reads the tables of pointers that `JMP ($nnnn)`
+    /// dispatches through, when their length is given by a bounds check.
+    pub fn follow_jmp_indirect_table(mut self, follow_jmp_indirect_table: bool) -> Self {
+        self.follow_jmp_indirect_table = follow_jmp_indirect_table;
+        self
+    }
+
+    /// Reads from the file `regions` the ranges of the PRG that are code or
+    /// data whatever the CDL says, one per line as `C100-C2FF code` or
+    /// `1:0000-00FF data`.
+    pub fn regions(mut self, regions: Option<String>) -> Self {
+        self.regions = regions;
+        self
+    }
+
+    /// Traces the code from these entry points, in addition to the vectors
+    /// with `trace`.
+    pub fn entries(mut self, entries: Vec<Location>) -> Self {
+        self.entries = entries;
+        self
+    }
+
+    /// Writes the control-flow graph of the routine at `cfg` as a DOT file.
+    pub fn cfg(mut self, cfg: Option<Location>) -> Self {
+        self.cfg = cfg;
+        self
+    }
+
+    /// Names with `.ENUM` the zero-page pointers used by `(zp),Y`
+    /// (`ptr_XX`).
+    pub fn zp_pointers(mut self, zp_pointers: bool) -> Self {
+        self.zp_pointers = zp_pointers;
+        self
+    }
+
+    /// Emits as `.dw` the runs of data words pointing into the ROM, as labels
+    /// when their bank is known.
+    pub fn pointer_tables(mut self, pointer_tables: bool) -> Self {
+        self.pointer_tables = pointer_tables;
+        self
+    }
+
+    /// Looks for the pointer tables right after every single byte of data,
+    /// instead of every other byte from the start of the data, so a table
+    /// after an odd number of bytes is still found.
+    pub fn word_align(mut self, word_align: bool) -> Self {
+        self.word_align = word_align;
+        self
+    }
+
+    /// Fails instead of guessing when the ROM isn't fully supported.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Names with `.DEFINE` the immediate values and zero-page addresses used
+    /// at least `threshold` times.
+    pub fn define_constants(mut self, threshold: Option<usize>) -> Self {
+        self.define_constants = threshold;
+        self
+    }
+
+    /// Emits as `.db` the opcodes whose operand is flagged as data by the CDL,
+    /// instead of only warning about it.
+    pub fn operand_data_as_db(mut self, operand_data_as_db: bool) -> Self {
+        self.operand_data_as_db = operand_data_as_db;
+        self
+    }
+
+    /// Variant of the 6502 the instructions are decoded for, the NMOS 6502
+    /// of the NES by default.
+    pub fn cpu(mut self, cpu: Cpu) -> Self {
+        self.cpu = cpu;
+        self
+    }
+
+    /// Assembler the output is written for, WLA DX by default.
+    pub fn assembler(mut self, assembler: Assembler) -> Self {
+        self.assembler = assembler;
+        self
+    }
+
+    /// Prefix of the hexadecimal numbers, `$` by default.
+    pub fn hex_prefix(mut self, hex_prefix: HexPrefix) -> Self {
+        self.hex_prefix = hex_prefix;
+        self
+    }
+
+    /// Writes the hexadecimal digits, including the ones of the labels, in
+    /// lowercase.
+    pub fn lowercase_hex(mut self, lowercase_hex: bool) -> Self {
+        self.lowercase_hex = lowercase_hex;
+        self
+    }
+
+    /// Skips the CHR banks: no `.chr` files and no CHR in the memory map.
+    pub fn no_chr(mut self, no_chr: bool) -> Self {
+        self.no_chr = no_chr;
+        self
+    }
+
+    /// Skips the CHR banks like `no_chr`, but describes them in `chr.txt`.
+    pub fn prg_only(mut self, prg_only: bool) -> Self {
+        self.prg_only = prg_only;
+        self
+    }
+
+    /// Size of the PRG banks in bytes, 8KB, 16KB (the default) or 32KB,
+    /// for the mappers switching 8KB or 32KB at once.
+    pub fn bank_size(mut self, bank_size: Option<usize>) -> Self {
+        self.bank_size = bank_size;
+        self
+    }
+
+    /// Only disassembles the PRG banks from `start` to `end` (inclusive).
+    pub fn bank_range(mut self, start: Option<u8>, end: Option<u8>) -> Self {
+        self.start_bank = start;
+        self.end_bank = end;
+        self
+    }
+
+    /// Gives the PRG bank `i` the number `order[i]` in the output: its file,
+    /// its WLA bank and its labels, the references following it. The fixed
+    /// bank is still the last one of the ROM, so `$C000-$FFFF` resolves to
+    /// the number `order` gives it, which should stay the last one for the
+    /// reassembled ROM to run. The bank range keeps the numbers of the ROM.
+    pub fn bank_order(mut self, order: Vec<u8>) -> Self {
+        self.bank_order = order;
+        self
+    }
+
+    /// Disassembles as code every byte of the banks where the CDL logged
+    /// nothing, instead of writing them as unknown bytes.
+    pub fn linear_unlogged_banks(mut self, linear_unlogged_banks: bool) -> Self {
+        self.linear_unlogged_banks = linear_unlogged_banks;
+        self
+    }
+
+    /// Names the illegal opcodes in the comment of their `.db`, without
+    /// decoding them (see `cpu` for that).
+    pub fn opcode_illegals_as_comment(mut self, opcode_illegals_as_comment: bool) -> Self {
+        self.opcode_illegals_as_comment = opcode_illegals_as_comment;
+        self
+    }
+
+    /// Comments the data read by the code (`LDA table,X`) with the
+    /// instructions reading it.
+    pub fn data_xref(mut self, data_xref: bool) -> Self {
+        self.data_xref = data_xref;
+        self
+    }
+
+    /// Indents the instructions, the data and the directives with `indent`
+    /// instead of four spaces, the labels staying at the start of the line.
+    pub fn indent(mut self, indent: Option<String>) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Writes a file for every CHR bank, even the identical ones.
+    pub fn no_dedupe(mut self, no_dedupe: bool) -> Self {
+        self.no_dedupe = no_dedupe;
+        self
+    }
+
+    /// Doesn't comment the bytes flagged as both code and data by the CDL.
+    pub fn no_code_data_comments(mut self, no_code_data_comments: bool) -> Self {
+        self.no_code_data_comments = no_code_data_comments;
+        self
+    }
+
+    /// Writes every routine to its own file, named after its label, that the
+    /// bank file includes.
+    pub fn split_routines(mut self, split_routines: bool) -> Self {
+        self.split_routines = split_routines;
+        self
+    }
+
+    /// Writes up to `max` data bytes per `.db` line, a label starting a new
+    /// line.
+    pub fn max_line_bytes(mut self, max: Option<usize>) -> Self {
+        self.max_line_bytes = max;
+        self
+    }
+
+    /// Comments every instruction with its bytes, e.g. `LDA $2002 ; AD 02 20`.
+    pub fn raw_bytes(mut self, raw_bytes: bool) -> Self {
+        self.raw_bytes = raw_bytes;
+        self
+    }
+
+    /// Comments the data and the unknown bytes with their hex dump, 16 bytes
+    /// per row with their ASCII.
+    pub fn hexdump(mut self, hexdump: bool) -> Self {
+        self.hexdump = hexdump;
+        self
+    }
+
+    /// Comments every branch with its offset, e.g. `BNE L00C100 ; -6`.
+    pub fn branch_offsets(mut self, branch_offsets: bool) -> Self {
+        self.branch_offsets = branch_offsets;
+        self
+    }
+
+    /// Comments every branch with its direction, the backward ones being
+    /// loops.
+    pub fn branch_direction(mut self, branch_direction: bool) -> Self {
+        self.branch_direction = branch_direction;
+        self
+    }
+
+    /// Also names every label of a bank after its CPU address only, local to
+    /// the bank's section (`_LC100`), which the bank uses for its own labels.
+    pub fn local_bank_labels(mut self, local_bank_labels: bool) -> Self {
+        self.local_bank_labels = local_bank_labels;
+        self
+    }
+
+    /// Names the labels of the bytes that aren't code with `prefix` instead
+    /// of `L`.
+    pub fn data_label_prefix(mut self, prefix: Option<String>) -> Self {
+        self.data_label_prefix = prefix;
+        self
+    }
+
+    /// Comments every label with its CPU address.
+    pub fn label_comments(mut self, label_comments: bool) -> Self {
+        self.label_comments = label_comments;
+        self
+    }
+
+    /// Warns about the labels referenced but defined in no bank.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Suffixes the zero-page operands with `.b` and the absolute ones with
+    /// `.w`, so the assembler doesn't have to guess. On by default.
+    pub fn explicit_size(mut self, explicit_size: bool) -> Self {
+        self.implicit_size = !explicit_size;
+        self
+    }
+
+    /// Looks the CRC32 of the PRG up in the file `rom_db`, lines of
+    /// `CRC32,title[,hint]`, to name the game in the header.
+    pub fn rom_db(mut self, rom_db: Option<String>) -> Self {
+        self.rom_db = rom_db;
+        self
+    }
+
+    /// Writes the content of the file `memory_map` instead of the generated
+    /// `.MEMORYMAP` and `.ROMBANKMAP`.
+    pub fn memory_map(mut self, memory_map: Option<String>) -> Self {
+        self.memory_map = memory_map;
+        self
+    }
+
+    /// Comments the stores of an immediate value into the PPU and APU
+    /// registers with the meaning of its bits.
+    pub fn decode_regs(mut self, decode_regs: bool) -> Self {
+        self.decode_regs = decode_regs;
+        self
+    }
+
+    /// Also writes every CHR bank as a PNG, 16 tiles per row.
+    pub fn chr_png(mut self, chr_png: bool) -> Self {
+        self.chr_png = chr_png;
+        self
+    }
+
+    /// Colors of the pixels of the CHR PNG files.
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Writes every CHR bank to a single PNG, in the output directory,
+    /// with `tiles_per_row` tiles per row.
+    pub fn chr_sheet(mut self, chr_sheet: Option<String>, tiles_per_row: usize) -> Self {
+        self.chr_sheet = chr_sheet;
+        self.tiles_per_row = tiles_per_row;
+        self
+    }
+
+    /// Disassembles without writing any file, `disassemble` only returning
+    /// the summary.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Emulator that wrote the CDL, for the bits they don't share.
+    pub fn cdl_format(mut self, cdl_format: CdlFormat) -> Self {
+        self.cdl_format = cdl_format;
+        self
+    }
+
+    /// Where the files are written in the output, all together by default.
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// How the assembler may place the sections, `FORCE` by default.
+    pub fn section_mode(mut self, section_mode: SectionMode) -> Self {
+        self.section_mode = section_mode;
+        self
+    }
+
+    /// Writes, in the output directory, the disassembly of the PRG banks as
+    /// JSON: for every bank, its lines with their address, kind, bytes,
+    /// mnemonic, operand, target and label.
+    #[cfg(feature = "serde")]
+    pub fn json(mut self, json: Option<String>) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// Comments the well-known sequences of instructions, like the
+    /// initialization at the start of the reset routine.
+    pub fn annotate_idioms(mut self, annotate_idioms: bool) -> Self {
+        self.annotate_idioms = annotate_idioms;
+        self
+    }
+
+    /// Names the sections `prefix_header`, `prefix_bank0`... instead of
+    /// `Header`, `Bank0`...
+    pub fn section_prefix(mut self, section_prefix: Option<String>) -> Self {
+        self.section_prefix = section_prefix;
+        self
+    }
+
+    /// Lists the files written, with their size, in `manifest.txt`.
+    pub fn manifest(mut self, manifest: bool) -> Self {
+        self.manifest = manifest;
+        self
+    }
+
+    /// Writes the runs of at least `threshold` unknown bytes to their own
+    /// file (`bankNNN.blobMM.bin`), included with `.INCBIN`.
+    pub fn incbin_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.incbin_threshold = threshold;
+        self
+    }
+
+    /// Also writes `flat.asm`, the banks sorted by CPU address with the
+    /// addresses between them reserved by `.ds`. Every bank selected must be
+    /// at its own address.
+    pub fn flat(mut self, flat: bool) -> Self {
+        self.flat = flat;
+        self
+    }
+
+    /// Places the PRG banks at their CPU address with `.ORGA`, the swappable
+    /// ones in an additional slot at `$8000`.
+    pub fn org_per_section(mut self, org_per_section: bool) -> Self {
+        self.org_per_section = org_per_section;
+        self
+    }
+
+    /// Writes, in the output directory, a Ghidra script creating the labels.
+    pub fn ghidra(mut self, ghidra: Option<String>) -> Self {
+        self.ghidra = ghidra;
+        self
+    }
+
+    /// Writes, in the output directory, the CPU address, opcode, mnemonic and
+    /// addressing mode of every instruction, separated by tabs.
+    pub fn tags(mut self, tags: Option<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Writes the labels, with their ROM offset and CPU address, to
+    /// `labels.csv`.
+    pub fn labels_csv(mut self, labels_csv: bool) -> Self {
+        self.labels_csv = labels_csv;
+        self
+    }
+
+    pub(crate) fn is_bank_selected(&self, id: u8) -> bool {
+        self.start_bank.is_none_or(|start| id >= start) && self.end_bank.is_none_or(|end| id <= end)
+    }
+
+    pub(crate) fn hex_digits(&self, value: usize, width: usize) -> String {
+        if self.lowercase_hex {
+            format!("{value:0width$x}")
+        } else {
+            format!("{value:0width$X}")
+        }
+    }
+
+    /// Formats a number in hexadecimal, with at least `width` digits.
+    pub(crate) fn hex(&self, value: usize, width: usize) -> String {
+        let digits = self.hex_digits(value, width);
+        match self.hex_prefix {
+            HexPrefix::Dollar => format!("${digits}"),
+            HexPrefix::ZeroX => format!("0x{digits}"),
+        }
+    }
+
+    /// Returns a zero-page operand, with its size if it must be explicit.
+    pub(crate) fn zero_page(&self, operand: String) -> String {
+        if self.implicit_size || !self.assembler.has_sections() {
+            operand
+        } else {
+            format!("{operand}.b")
+        }
+    }
+
+    /// Returns an absolute operand, with its size if it must be explicit.
+    pub(crate) fn absolute(&self, operand: String) -> String {
+        if self.implicit_size || !self.assembler.has_sections() {
+            operand
+        } else {
+            format!("{operand}.w")
+        }
+    }
+
+    /// Returns the name of a label as written in the bank `id`: local when
+    /// it's in that bank and `local_bank_labels` is set.
+    pub(crate) fn bank_label(&self, id: u8, target: usize, data_labels: DataLabels) -> String {
+        if self.has_local_labels() && target >> 16 == id as usize {
+            let prefix = self.label_prefix(target, data_labels);
+            format!("_{prefix}{}", self.hex_digits(target & 0xFFFF, 4))
+        } else {
+            self.label(target, data_labels)
+        }
+    }
+
+    /// Whether the labels are also local to their bank, which needs the
+    /// sections of WLA.
+    pub(crate) fn has_local_labels(&self) -> bool {
+        self.local_bank_labels && self.assembler.has_sections()
+    }
+
+    /// The indentation of everything but the labels and the comments, four
+    /// spaces by default.
+    pub(crate) fn indentation(&self) -> &str {
+        self.indent.as_deref().unwrap_or("    ")
+    }
+
+    /// A variable of `size` bytes, indented once more than the section or
+    /// enum it is in.
+    pub(crate) fn variable(&self, name: &str, size: usize) -> String {
+        format!(
+            "{0}{0}{1}",
+            self.indentation(),
+            self.assembler.variable(name, size)
+        )
+    }
+
+    /// The path of a file of the PRG banks (`bankNNN.asm`, the routines and
+    /// the blobs), relative to the output.
+    pub(crate) fn bank_path(&self, name: &str) -> String {
+        match self.layout {
+            Layout::Flat => name.to_string(),
+            Layout::Nested => format!("banks/{name}"),
+        }
+    }
+
+    /// The path of a file of the CHR banks, relative to the output.
+    pub(crate) fn chr_path(&self, name: &str) -> String {
+        match self.layout {
+            Layout::Flat => name.to_string(),
+            Layout::Nested => format!("chr/{name}"),
+        }
+    }
+
+    /// Whether the CHR banks are left out of the disassembly.
+    pub(crate) fn skips_chr(&self) -> bool {
+        self.no_chr || self.prg_only
+    }
+
+    /// Returns the name of a section, with the prefix if any.
+    pub(crate) fn section_name(&self, name: &str) -> String {
+        match &self.section_prefix {
+            Some(prefix) => format!("{prefix}_{}", name.to_lowercase()),
+            None => name.to_string(),
+        }
+    }
+
+    /// Returns the name of a label, with the data label prefix if it is in
+    /// `data_labels`.
+    pub(crate) fn label(&self, target: usize, data_labels: DataLabels) -> String {
+        let id = self.logical_bank((target >> 16) as u8) as usize;
+        format!(
+            "{}{}",
+            self.label_prefix(target, data_labels),
+            self.hex_digits(id << 16 | target & 0xFFFF, 6)
+        )
+    }
+
+    /// The number of the PRG bank `id` in the output, with `bank_order`.
+    pub(crate) fn logical_bank(&self, id: u8) -> u8 {
+        self.bank_order.get(id as usize).copied().unwrap_or(id)
+    }
+
+    /// `L`, or the data label prefix if the label isn't on code.
+    fn label_prefix(&self, target: usize, data_labels: DataLabels) -> &str {
+        match &self.data_label_prefix {
+            Some(prefix) if data_labels.contains(target) => prefix,
+            _ => "L",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum HexPrefix {
+    /// `$FF`, the WLA convention
+    #[default]
+    Dollar,
+    /// `0xFF`
+    ZeroX,
+}
+
+impl FromStr for HexPrefix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "$" | "dollar" => Ok(HexPrefix::Dollar),
+            "0x" => Ok(HexPrefix::ZeroX),
+            _ => Err(format!("invalid prefix '{s}', expected '$' or '0x'")),
+        }
+    }
+}
+
+/// The assembler the output is written for.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Assembler {
+    /// WLA DX (`wla-6502`)
+    #[default]
+    Wla,
+    /// asm6 and asm6f, writing the ROM in order with `.base` instead of banks
+    /// and sections
+    Asm6,
+}
+
+impl Assembler {
+    /// The operand of the instructions working on the accumulator (`ASL`,
+    /// `LSR`, `ROL` and `ROR`), that some assemblers want explicit.
+    pub(crate) fn accumulator(&self) -> &'static str {
+        match self {
+            Assembler::Wla => "",
+            Assembler::Asm6 => "A",
+        }
+    }
+
+    /// An operand that must be absolute even below $100, asm6 having no size
+    /// suffix.
+    pub(crate) fn word(&self, operand: String) -> String {
+        match self {
+            Assembler::Wla => format!("{operand}.w"),
+            Assembler::Asm6 => operand,
+        }
+    }
+
+    /// Whether the output is placed with `.MEMORYMAP`, `.BANK` and
+    /// `.SECTION`.
+    pub(crate) fn has_sections(&self) -> bool {
+        *self == Assembler::Wla
+    }
+
+    /// `directive`, written in uppercase for WLA and lowercase for asm6.
+    fn directive(&self, directive: &str) -> String {
+        match self {
+            Assembler::Wla => directive.to_uppercase(),
+            Assembler::Asm6 => directive.to_lowercase(),
+        }
+    }
+
+    /// Includes the source file `name`.
+    pub(crate) fn include(&self, name: &str) -> String {
+        format!("{} \"{name}\"", self.directive(".include"))
+    }
+
+    /// Includes the binary file `name`.
+    pub(crate) fn incbin(&self, name: &str) -> String {
+        format!("{} \"{name}\"", self.directive(".incbin"))
+    }
+
+    /// Names the constant `value`.
+    pub(crate) fn define(&self, name: &str, value: &str) -> String {
+        match self {
+            Assembler::Wla => format!(".DEFINE {name} {value}"),
+            Assembler::Asm6 => format!("{name} = {value}"),
+        }
+    }
+
+    /// Starts the variables at `addr`, closed by `enum_end`.
+    pub(crate) fn enum_start(&self, addr: &str) -> String {
+        format!("{} {addr}", self.directive(".enum"))
+    }
+
+    pub(crate) fn enum_end(&self) -> String {
+        self.directive(".ende")
+    }
+
+    /// A variable of `size` bytes, between `enum_start` and `enum_end`.
+    pub(crate) fn variable(&self, name: &str, size: usize) -> String {
+        match (self, size) {
+            (Assembler::Wla, 1) => format!("{name} db"),
+            (Assembler::Wla, 2) => format!("{name} dw"),
+            (Assembler::Wla, _) => format!("{name} dsb {size}"),
+            (Assembler::Asm6, _) => format!("{name} .dsb {size}"),
+        }
+    }
+
+    /// The directive reserving bytes.
+    pub(crate) fn reserve(&self) -> &'static str {
+        match self {
+            Assembler::Wla => ".ds",
+            Assembler::Asm6 => ".dsb",
+        }
+    }
+}
+
+impl FromStr for Assembler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "wla" | "wla-dx" => Ok(Assembler::Wla),
+            "asm6" | "asm6f" => Ok(Assembler::Asm6),
+            _ => Err(format!("invalid assembler '{s}', expected 'wla' or 'asm6'")),
+        }
+    }
+}
+
+/// The variant of the 6502 the instructions are decoded for.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Cpu {
+    /// the official instructions of the NMOS 6502, the CPU of the NES
+    #[default]
+    Nmos6502,
+    /// the CMOS 65C02 and its new instructions
+    Cmos65c02,
+    /// the NMOS 6502 with its stable illegal opcodes
+    NmosIllegal,
+}
+
+impl Cpu {
+    /// The instructions of the CPU, indexed by opcode.
+    pub fn opcodes(&self) -> &'static [Option<Opcode>; 256] {
+        match self {
+            Cpu::Nmos6502 => &OPCODES,
+            Cpu::Cmos65c02 => &CMOS_OPCODES,
+            Cpu::NmosIllegal => &ILLEGAL_OPCODES,
+        }
+    }
+
+    /// Returns the instruction encoded by `byte`, `None` for the invalid
+    /// opcodes.
+    pub fn decode(&self, byte: u8) -> Option<&'static Opcode> {
+        self.opcodes()[byte as usize].as_ref()
+    }
+}
+
+impl FromStr for Cpu {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nmos6502" | "6502" => Ok(Cpu::Nmos6502),
+            "65c02" => Ok(Cpu::Cmos65c02),
+            "nmos-illegal" => Ok(Cpu::NmosIllegal),
+            _ => Err(format!(
+                "invalid CPU '{s}', expected 'nmos6502', '65c02' or 'nmos-illegal'"
+            )),
+        }
+    }
+}
+
+/// The emulator that wrote a CDL. Both flag code (bit 0), data (bit 1) and
+/// the data read indirectly (bit 5), but FCEUX uses bits 2-3 for the bank
+/// the byte was mapped at, where Mesen flags the jump targets and the
+/// subroutines.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CdlFormat {
+    #[default]
+    Fceux,
+    Mesen,
+}
+
+impl FromStr for CdlFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fceux" => Ok(CdlFormat::Fceux),
+            "mesen" => Ok(CdlFormat::Mesen),
+            _ => Err(format!(
+                "invalid CDL format '{s}', expected 'fceux' or 'mesen'"
+            )),
+        }
+    }
+}
+
+/// The kind of the `.SECTION`s, telling WLA where it may place them: at
+/// their `.ORG` (`FORCE`), anywhere in their bank (`FREE`), in their bank or
+/// a later one (`SEMIFREE`), or in any bank (`SUPERFREE`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SectionMode {
+    #[default]
+    Force,
+    Free,
+    SemiFree,
+    SuperFree,
+}
+
+impl FromStr for SectionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "force" => Ok(SectionMode::Force),
+            "free" => Ok(SectionMode::Free),
+            "semifree" => Ok(SectionMode::SemiFree),
+            "superfree" => Ok(SectionMode::SuperFree),
+            _ => Err(format!(
+                "invalid section mode '{s}', expected 'force', 'free', 'semifree' or 'superfree'"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SectionMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keyword = match self {
+            SectionMode::Force => "FORCE",
+            SectionMode::Free => "FREE",
+            SectionMode::SemiFree => "SEMIFREE",
+            SectionMode::SuperFree => "SUPERFREE",
+        };
+        f.write_str(keyword)
+    }
+}
+
+/// Where the files are written in the output: all in the same directory
+/// (`flat`), or the PRG banks in `banks/` and the CHR ones in `chr/`
+/// (`nested`). The paths included are relative to the output either way.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Layout {
+    #[default]
+    Flat,
+    Nested,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "flat" => Ok(Layout::Flat),
+            "nested" => Ok(Layout::Nested),
+            _ => Err(format!("invalid layout '{s}', expected 'flat' or 'nested'")),
+        }
+    }
+}
+
+/// The colors of the 4 values of a CHR pixel, a greyscale ramp by default.
+/// Parsed from `RRGGBB,RRGGBB,RRGGBB,RRGGBB`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Palette(pub [[u8; 3]; 4]);
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette([[0x00; 3], [0x55; 3], [0xAA; 3], [0xFF; 3]])
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let colors: Vec<&str> = s.split(',').collect();
+        if colors.len() != 4 {
+            return Err(format!("expected 4 colors, got {}", colors.len()));
+        }
+
+        let mut palette = [[0u8; 3]; 4];
+        for (rgb, color) in palette.iter_mut().zip(colors) {
+            let digits = color.trim().trim_start_matches('#');
+            let value = u32::from_str_radix(digits, 16)
+                .ok()
+                .filter(|_| digits.len() == 6)
+                .ok_or_else(|| format!("invalid color '{color}', expected RRGGBB"))?;
+            rgb.copy_from_slice(&value.to_be_bytes()[1..]);
+        }
+
+        Ok(Palette(palette))
+    }
+}
+
+/// An address in the PRG, either as seen by the CPU (`$C123`), or as a bank
+/// and an offset inside of it (`1:0123`).
+///
+/// CPU addresses below `$C000` are assumed to be in the first bank.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Location {
+    Cpu(u16),
+    Bank(u8, u16),
+}
+
+impl FromStr for Location {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn hex(s: &str) -> Result<u16, String> {
+            let digits = s
+                .strip_prefix('$')
+                .or_else(|| s.strip_prefix("0x"))
+                .unwrap_or(s);
+            u16::from_str_radix(digits, 16).map_err(|e| format!("invalid address '{s}': {e}"))
+        }
+
+        match s.split_once(':') {
+            Some((bank, offset)) => {
+                let bank = bank
+                    .parse()
+                    .map_err(|e| format!("invalid bank '{bank}': {e}"))?;
+                Ok(Location::Bank(bank, hex(offset)?))
+            }
+            None => Ok(Location::Cpu(hex(s)?)),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Cpu(addr) => write!(f, "${addr:04X}"),
+            Location::Bank(id, offset) => write!(f, "{id}:{offset:04X}"),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::Location;
+use crate::RomData;
+use crate::prg_offset;
+use crate::resolve_location;
+use std::io::Error;
+use std::io::ErrorKind;
+
+/// What a region of the PRG is, whatever the CDL says.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    Code,
+    Data,
+}
+
+/// A range of the PRG (inclusive), from a line like `C100-C2FF code` or
+/// `1:0000-00FF data`.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Region {
+    start: Location,
+    end: Location,
+    kind: Kind,
+}
+
+/// Parses a region file, one region per line, the empty lines and the ones
+/// starting with `;` or `#` being skipped.
+pub(crate) fn parse_regions(text: &str) -> Result<Vec<Region>, Box<dyn std::error::Error>> {
+    let mut regions = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+
+        let region = parse_region(line)
+            .map_err(|e| invalid(format!("line {} of the regions: {e}", number + 1)))?;
+        regions.push(region);
+    }
+    Ok(regions)
+}
+
+fn parse_region(line: &str) -> Result<Region, String> {
+    let Some((range, kind)) = line.split_once(char::is_whitespace) else {
+        return Err(format!("expected 'START-END code|data', got '{line}'"));
+    };
+    let Some((start, end)) = range.split_once('-') else {
+        return Err(format!("expected a range START-END, got '{range}'"));
+    };
+    let kind = match kind.trim().to_ascii_lowercase().as_str() {
+        "code" => Kind::Code,
+        "data" => Kind::Data,
+        kind => return Err(format!("invalid kind '{kind}', expected 'code' or 'data'")),
+    };
+    let start = start.parse()?;
+    // `1:0000-00FF` is in the bank of its start
+    let end = match start {
+        Location::Bank(id, _) if !end.contains(':') => format!("{id}:{end}").parse()?,
+        _ => end.parse()?,
+    };
+    Ok(Region { start, end, kind })
+}
+
+/// Overrides the flags of the bytes of every region: code only, or data
+/// only.
+pub(crate) fn apply_regions(
+    regions: &[Region],
+    cdl: &mut [u8],
+    rom_data: RomData,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for region in regions {
+        let start = resolve_location(region.start, rom_data)?;
+        let end = resolve_location(region.end, rom_data)?;
+        let (Some(first), Some(last)) = (prg_offset(start, rom_data), prg_offset(end, rom_data))
+        else {
+            continue;
+        };
+        if first > last {
+            return Err(invalid(format!(
+                "the region {}-{} ends before it starts",
+                region.start, region.end
+            )));
+        }
+
+        log::debug!("{:?} from L{start:06X} to L{end:06X}", region.kind);
+        for flags in &mut cdl[first..=last] {
+            *flags = match region.kind {
+                Kind::Code => (*flags & !2) | 1,
+                Kind::Data => (*flags & !1) | 2,
+            };
+        }
+    }
+    Ok(())
+}
+
+fn invalid(message: String) -> Box<dyn std::error::Error> {
+    Box::new(Error::new(ErrorKind::InvalidInput, message))
+}
@@ -0,0 +1,96 @@
+use crate::Addressing;
+use crate::Opcode;
+
+/// The register and the value loaded by an immediate load (`LDA #value`),
+/// tracked to know what a following store writes.
+pub(crate) fn immediate_load(opcode: &Opcode, operand: u8) -> Option<(char, u8)> {
+    match (opcode.name, opcode.addressing) {
+        ("LDA", Addressing::Immediate) => Some(('A', operand)),
+        ("LDX", Addressing::Immediate) => Some(('X', operand)),
+        ("LDY", Addressing::Immediate) => Some(('Y', operand)),
+        _ => None,
+    }
+}
+
+/// The register written to memory by `STA`, `STX` or `STY`.
+pub(crate) fn stored_register(opcode: &Opcode) -> Option<char> {
+    match opcode.name {
+        "STA" => Some('A'),
+        "STX" => Some('X'),
+        "STY" => Some('Y'),
+        _ => None,
+    }
+}
+
+/// Describes the effect of writing `value` to the hardware register `addr`,
+/// for the registers whose bits are flags.
+pub(crate) fn describe_write(addr: usize, value: u8) -> Option<String> {
+    let bit = |n: u8| value & (1 << n) != 0;
+
+    let (name, description) = match addr {
+        0x2000 => {
+            let mut flags = vec![];
+            if bit(7) {
+                flags.push("NMI on".to_string());
+            }
+            if bit(5) {
+                flags.push("8x16 sprites".to_string());
+            }
+            if bit(4) {
+                flags.push("bg at $1000".to_string());
+            }
+            if bit(3) {
+                flags.push("sprites at $1000".to_string());
+            }
+            if bit(2) {
+                flags.push("increment 32".to_string());
+            }
+            flags.push(format!(
+                "nametable ${:04X}",
+                0x2000 + (value as usize & 3) * 0x400
+            ));
+            ("PPUCTRL", flags.join(" + "))
+        }
+        0x2001 => {
+            let names = [
+                "greyscale",
+                "bg in left column",
+                "sprites in left column",
+                "show bg",
+                "show sprites",
+                "emphasize red",
+                "emphasize green",
+                "emphasize blue",
+            ];
+            ("PPUMASK", join_flags(value, &names, "rendering off"))
+        }
+        0x4015 => {
+            let names = ["pulse 1", "pulse 2", "triangle", "noise", "DMC"];
+            ("APU status", join_flags(value, &names, "all channels off"))
+        }
+        0x4017 => {
+            let mode = if bit(7) { "5-step" } else { "4-step" };
+            let irq = if bit(6) { ", IRQ off" } else { "" };
+            ("APU frame counter", format!("{mode}{irq}"))
+        }
+        _ => return None,
+    };
+
+    Some(format!("{name}: {description}"))
+}
+
+/// Joins the names of the bits set, `none` if none is.
+fn join_flags(value: u8, names: &[&str], none: &str) -> String {
+    let flags: Vec<&str> = names
+        .iter()
+        .enumerate()
+        .filter(|(n, _)| value & (1 << n) != 0)
+        .map(|(_, name)| *name)
+        .collect();
+
+    if flags.is_empty() {
+        none.to_string()
+    } else {
+        flags.join(" + ")
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::io::Error;
+use std::io::ErrorKind;
+
+/// A known ROM, from a line `CRC32,title[,hint]` of the database.
+#[derive(Clone, Debug)]
+pub(crate) struct KnownRom {
+    pub(crate) title: String,
+    /// what helps to disassemble it, like its real mapper
+    pub(crate) hint: Option<String>,
+}
+
+/// Parses a database of ROMs identified by the CRC32 of their PRG, in
+/// hexadecimal. The empty lines and the ones starting with `#` are skipped.
+pub(crate) fn parse_rom_db(
+    text: &str,
+) -> Result<HashMap<u32, KnownRom>, Box<dyn std::error::Error>> {
+    let mut roms = HashMap::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.splitn(3, ',').map(str::trim);
+        let crc = fields.next().unwrap_or_default();
+        let crc = u32::from_str_radix(crc.trim_start_matches("0x"), 16).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "line {} of the ROM database: invalid CRC32 '{crc}': {e}",
+                    number + 1
+                ),
+            )
+        })?;
+        let title = fields.next().unwrap_or_default().to_string();
+        let hint = fields
+            .next()
+            .filter(|hint| !hint.is_empty())
+            .map(str::to_string);
+        roms.insert(crc, KnownRom { title, hint });
+    }
+    Ok(roms)
+}
@@ -0,0 +1,86 @@
+use crate::DataLabels;
+use crate::Options;
+use crate::RomData;
+use crate::TRAINER_SIZE;
+use crate::is_wram;
+use crate::prg_offset;
+use crate::wram_label;
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// Writes a Python script for Ghidra's script manager creating the labels.
+/// The swappable banks are expected in overlays named `BANKn`, the labels of
+/// the banks without an overlay going to the default address space.
+pub(crate) fn write_ghidra_script(
+    labels: &BTreeSet<usize>,
+    rom_data: RomData,
+    data_labels: DataLabels,
+    options: &Options,
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    writeln!(output, "# Labels exported by nes-disasm")?;
+    writeln!(output, "# @category NES\n")?;
+    writeln!(output, "LABELS = [")?;
+    for &label in labels {
+        if prg_offset(label, rom_data).is_some() {
+            let id = label >> 16;
+            let addr = label & 0xFFFF;
+            let name = options.label(label, data_labels);
+            writeln!(output, "    ({id}, 0x{addr:04X}, \"{name}\"),")?;
+        } else if options.wram_vars && is_wram(label) {
+            let name = wram_label(label);
+            writeln!(output, "    (None, 0x{label:04X}, \"{name}\"),")?;
+        }
+    }
+    writeln!(output, "]\n")?;
+
+    writeln!(output, "def address(bank, addr):")?;
+    writeln!(output, "    if bank is not None:")?;
+    writeln!(
+        output,
+        "        space = currentProgram.getAddressFactory().getAddressSpace(\"BANK%d\" % bank)"
+    )?;
+    writeln!(output, "        if space is not None:")?;
+    writeln!(output, "            return space.getAddress(addr)")?;
+    writeln!(output, "    return toAddr(addr)\n")?;
+
+    writeln!(output, "for bank, addr, name in LABELS:")?;
+    writeln!(output, "    createLabel(address(bank, addr), name, True)")
+}
+
+/// Writes the labels as `rom_offset,cpu_address,bank,label`. The offset
+/// counts the iNES header and the `trainer`, and is empty like the bank for
+/// the RAM. The bank is the one of the label's name, with `--bank-order`.
+pub(crate) fn write_csv(
+    labels: &BTreeSet<usize>,
+    rom_data: RomData,
+    data_labels: DataLabels,
+    trainer: bool,
+    options: &Options,
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    let header_size = 0x10 + if trainer { TRAINER_SIZE } else { 0 };
+    writeln!(output, "rom_offset,cpu_address,bank,label")?;
+    for &label in labels {
+        let addr = label & 0xFFFF;
+        match prg_offset(label, rom_data) {
+            Some(offset) => writeln!(
+                output,
+                "{},{},{},{}",
+                options.hex(offset + header_size, 5),
+                options.hex(addr, 4),
+                options.logical_bank((label >> 16) as u8),
+                options.label(label, data_labels)
+            )?,
+            None => {
+                let name = if options.wram_vars && is_wram(label) {
+                    wram_label(label)
+                } else {
+                    options.hex(addr, 4)
+                };
+                writeln!(output, ",{},,{name}", options.hex(addr, 4))?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use crate::DataLabels;
+use crate::Options;
+use crate::RomData;
+use crate::decode_one;
+use crate::offset_to_label;
+use std::io::Write;
+
+/// Writes a line `cpu_addr<TAB>opcode<TAB>mnemonic<TAB>addr_mode` for every
+/// instruction of the selected banks, decoded the same way as the text
+/// output. The data and the unknown bytes are left out.
+pub(crate) fn write_tags(
+    prg: &[u8],
+    cdl: &[u8],
+    rom_data: RomData,
+    options: &Options,
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    writeln!(output, "cpu_addr\topcode\tmnemonic\taddr_mode")?;
+    let mut offset = 0;
+    while offset < prg.len() {
+        let position = offset_to_label(offset, rom_data);
+        let selected = options.is_bank_selected((position >> 16) as u8);
+        let Some(instruction) = decode_one(prg, position, rom_data, DataLabels::default(), options)
+            .filter(|_| selected && (cdl[offset] & 1) == 1)
+            .filter(|instruction| {
+                // the same as the text output
+                let operand = &cdl[offset + 1..offset + 1 + instruction.size];
+                !options.operand_data_as_db || !operand.iter().any(|f| (f & 3) == 2)
+            })
+        else {
+            offset += 1;
+            continue;
+        };
+
+        writeln!(
+            output,
+            "{}\t{}\t{}\t{:?}",
+            options.hex_digits(position & 0xFFFF, 4),
+            options.hex_digits(prg[offset] as usize, 2),
+            instruction.opcode.name,
+            instruction.opcode.addressing
+        )?;
+        offset += instruction.size + 1;
+    }
+    Ok(())
+}
@@ -0,0 +1,427 @@
+use crate::Addressing;
+use crate::Annotations;
+use crate::Cpu;
+use crate::DataLabels;
+use crate::Options;
+use crate::RomData;
+use crate::cpu_to_label;
+use crate::decode_one;
+use crate::offset_to_label;
+use crate::prg_offset;
+use std::collections::HashSet;
+
+const LDA_ABSOLUTE_X: u8 = 0xBD;
+const LDA_ABSOLUTE_Y: u8 = 0xB9;
+const PHA: u8 = 0x48;
+const STA_ZERO_PAGE: u8 = 0x85;
+const STA_ABSOLUTE: u8 = 0x8D;
+const CMP_IMMEDIATE: u8 = 0xC9;
+const CPX_IMMEDIATE: u8 = 0xE0;
+const CPY_IMMEDIATE: u8 = 0xC0;
+/// how far back the instructions filling the pointer of `JMP ($nnnn)` are
+/// looked for
+const INDIRECT_WINDOW: usize = 16;
+
+/// Marks as code in `cdl` every byte reachable from `entries` and, with
+/// `--trace`, from the interrupt vectors.
+pub(crate) fn trace(
+    prg: &[u8],
+    cdl: &mut [u8],
+    rom_data: RomData,
+    options: &Options,
+    entries: &[usize],
+    annotations: &mut Annotations,
+) {
+    let mut roots = entries.to_vec();
+    if options.trace {
+        let last = rom_data.banks_count - 1;
+        let vectors = prg.len() - 6;
+        for vector in 0..3 {
+            let addr = read_word(prg, vectors + vector * 2);
+            roots.push(cpu_to_label(last, addr, rom_data));
+        }
+    }
+    annotations.labels.extend(&roots);
+
+    follow_roots(roots, prg, cdl, rom_data, options, annotations);
+}
+
+/// Follows the subroutine calls, jumps and branches of the code flagged by
+/// the CDL, so the routines never executed while logging are found too.
+pub(crate) fn follow_calls(
+    prg: &[u8],
+    cdl: &mut [u8],
+    rom_data: RomData,
+    options: &Options,
+    annotations: &mut Annotations,
+) {
+    let mut roots = vec![];
+    let mut offset = 0;
+    while offset < prg.len() {
+        if (cdl[offset] & 1) == 0 {
+            offset += 1;
+            continue;
+        }
+
+        let position = offset_to_label(offset, rom_data);
+        let Some(instruction) = decode_one(prg, position, rom_data, DataLabels::default(), options)
+        else {
+            offset += 1;
+            continue;
+        };
+
+        let target = instruction
+            .rom_target()
+            .filter(|target| prg_offset(*target, rom_data).is_some_and(|t| (cdl[t] & 1) == 0));
+        if let Some(target) = target {
+            log::debug!(
+                "{} at L{position:06X} to the unlogged L{target:06X}",
+                instruction.opcode.name
+            );
+            roots.push(target);
+        }
+        offset += instruction.size + 1;
+    }
+
+    follow_roots(roots, prg, cdl, rom_data, options, annotations);
+}
+
+fn follow_roots(
+    mut roots: Vec<usize>,
+    prg: &[u8],
+    cdl: &mut [u8],
+    rom_data: RomData,
+    options: &Options,
+    annotations: &mut Annotations,
+) {
+    let mut visited = HashSet::new();
+    while let Some(root) = roots.pop() {
+        follow(
+            root,
+            prg,
+            cdl,
+            rom_data,
+            options,
+            annotations,
+            &mut visited,
+            &mut roots,
+        );
+    }
+}
+
+/// Decodes instructions from `position` until the flow of execution stops,
+/// adding the targets of jumps and branches to `roots`.
+#[allow(clippy::too_many_arguments)]
+fn follow(
+    mut position: usize,
+    prg: &[u8],
+    cdl: &mut [u8],
+    rom_data: RomData,
+    options: &Options,
+    annotations: &mut Annotations,
+    visited: &mut HashSet<usize>,
+    roots: &mut Vec<usize>,
+) {
+    // the last instructions decoded, used to recognize idioms.
+    let mut previous: Vec<(u8, usize, usize)> = vec![];
+
+    while visited.insert(position) {
+        let Some(offset) = prg_offset(position, rom_data) else {
+            break;
+        };
+
+        // trust the CDL when it says it's data
+        if (cdl[offset] & 3) == 2 {
+            break;
+        }
+
+        let Some(instruction) = decode_one(prg, position, rom_data, DataLabels::default(), options)
+        else {
+            break;
+        };
+
+        for byte in &mut cdl[offset..=offset + instruction.size] {
+            *byte |= 1;
+        }
+
+        previous.push((prg[offset], instruction.value, position));
+
+        let id = (position >> 16) as u8;
+        let rom_target = instruction.rom_target();
+        match (instruction.opcode.name, &instruction.opcode.addressing) {
+            ("JMP", Addressing::Absolute) => {
+                roots.extend(rom_target);
+                break;
+            }
+            ("BRA", _) => {
+                roots.extend(rom_target);
+                break;
+            }
+            ("JSR", _) | (_, Addressing::Relative) => roots.extend(rom_target),
+            ("RTS", _) => {
+                jump_table(
+                    &previous,
+                    id,
+                    prg,
+                    cdl,
+                    rom_data,
+                    options,
+                    annotations,
+                    roots,
+                );
+                break;
+            }
+            ("JMP", Addressing::Indirect) => {
+                rom_pointer(
+                    position,
+                    instruction.value,
+                    prg,
+                    cdl,
+                    rom_data,
+                    options,
+                    annotations,
+                    roots,
+                );
+                if options.follow_jmp_indirect_table {
+                    indirect_jump_table(
+                        &previous,
+                        instruction.value,
+                        prg,
+                        cdl,
+                        rom_data,
+                        options,
+                        annotations,
+                        roots,
+                    );
+                }
+                break;
+            }
+            ("JMP", _) | ("RTI", _) | ("BRK", _) => break,
+            _ => {}
+        }
+
+        position = instruction.next();
+    }
+}
+
+/// Follows `JMP ($nnnn)` when the pointer is in the ROM, so its value is
+/// known. As the NMOS 6502 does, a pointer at `$xxFF` has its high byte read
+/// from `$xx00`.
+#[allow(clippy::too_many_arguments)]
+fn rom_pointer(
+    user: usize,
+    pointer: usize,
+    prg: &[u8],
+    cdl: &mut [u8],
+    rom_data: RomData,
+    options: &Options,
+    annotations: &mut Annotations,
+    roots: &mut Vec<usize>,
+) {
+    let hi = if options.cpu == Cpu::Cmos65c02 {
+        pointer + 1
+    } else {
+        pointer & 0xFF00 | (pointer + 1) & 0xFF
+    };
+    if pointer < 0x8000 || hi > 0xFFFF {
+        return;
+    }
+
+    let id = (user >> 16) as u8;
+    let (Some(lo_offset), Some(hi_offset)) = (
+        prg_offset(cpu_to_label(id, pointer, rom_data), rom_data),
+        prg_offset(cpu_to_label(id, hi, rom_data), rom_data),
+    ) else {
+        return;
+    };
+    let addr = (prg[hi_offset] as usize) << 8 | prg[lo_offset] as usize;
+    if addr < 0x8000 {
+        return;
+    }
+
+    for offset in [lo_offset, hi_offset] {
+        if (cdl[offset] & 1) == 0 {
+            cdl[offset] |= 2;
+        }
+    }
+    let target = cpu_to_label(id, addr, rom_data);
+    log::debug!("JMP (${pointer:04X}) at L{user:06X} goes to L{target:06X}");
+    annotations.labels.insert(target);
+    roots.push(target);
+}
+
+/// Recognizes the "RTS trick": `LDA hi,X / PHA / LDA lo,X / PHA / RTS`. The
+/// tables hold the addresses minus one of the routines, their length being
+/// the distance between the two tables.
+#[allow(clippy::too_many_arguments)]
+fn jump_table(
+    previous: &[(u8, usize, usize)],
+    id: u8,
+    prg: &[u8],
+    cdl: &mut [u8],
+    rom_data: RomData,
+    options: &Options,
+    annotations: &mut Annotations,
+    roots: &mut Vec<usize>,
+) {
+    let [
+        ..,
+        (first, hi, user),
+        (PHA, _, _),
+        (second, lo, _),
+        (PHA, _, _),
+        _,
+    ] = previous
+    else {
+        return;
+    };
+    if first != second || (*first != LDA_ABSOLUTE_X && *first != LDA_ABSOLUTE_Y) {
+        return;
+    }
+
+    let count = hi.abs_diff(*lo);
+    if count == 0 || count > 0x100 || *lo < 0x8000 || *hi < 0x8000 {
+        return;
+    }
+
+    let lo_label = cpu_to_label(id, *lo, rom_data);
+    let hi_label = cpu_to_label(id, *hi, rom_data);
+
+    let mut entries = 0;
+    for entry in 0..count {
+        let (Some(lo_offset), Some(hi_offset)) = (
+            prg_offset(lo_label + entry, rom_data),
+            prg_offset(hi_label + entry, rom_data),
+        ) else {
+            break;
+        };
+
+        let addr = (((prg[hi_offset] as usize) << 8 | prg[lo_offset] as usize) + 1) & 0xFFFF;
+        if addr < 0x8000 {
+            break;
+        }
+
+        cdl[lo_offset] |= 2;
+        cdl[hi_offset] |= 2;
+
+        let target = cpu_to_label(id, addr, rom_data);
+        annotations.labels.insert(target);
+        roots.push(target);
+        entries += 1;
+    }
+
+    if entries == 0 {
+        return;
+    }
+
+    log::debug!("Jump table of {entries} entries at L{lo_label:06X}/L{hi_label:06X}");
+    annotations.labels.insert(*user);
+    annotations.labels.insert(lo_label);
+    annotations.labels.insert(hi_label);
+    let user = options.label(*user, DataLabels::default());
+    for (label, bytes) in [(lo_label, "low"), (hi_label, "high")] {
+        let comment = format!("jump table ({bytes} bytes, {entries} entries) used by {user}");
+        annotations.comments.entry(label).or_default().push(comment);
+    }
+}
+
+/// Recognizes `JMP ($nnnn)` whose pointer was loaded from tables in ROM:
+/// `LDA lo,X / STA ptr / LDA hi,X / STA ptr+1`, the tables being a single
+/// one of words when `hi` is `lo+1`. Their length comes from the bounds
+/// check of the index before (`CMP #n`, `CPX #n` or `CPY #n`).
+#[allow(clippy::too_many_arguments)]
+fn indirect_jump_table(
+    previous: &[(u8, usize, usize)],
+    pointer: usize,
+    prg: &[u8],
+    cdl: &mut [u8],
+    rom_data: RomData,
+    options: &Options,
+    annotations: &mut Annotations,
+    roots: &mut Vec<usize>,
+) {
+    let start = previous.len().saturating_sub(INDIRECT_WINDOW);
+    let window = &previous[start..];
+    let (_, _, user) = previous[previous.len() - 1];
+    let id = (user >> 16) as u8;
+
+    // the table read just before storing a byte of the pointer
+    let table_of = |addr: usize| {
+        window.windows(2).rev().find_map(|pair| match pair {
+            [(load, table, _), (STA_ZERO_PAGE | STA_ABSOLUTE, stored, _)]
+                if *stored == addr && (*load == LDA_ABSOLUTE_X || *load == LDA_ABSOLUTE_Y) =>
+            {
+                Some(*table)
+            }
+            _ => None,
+        })
+    };
+    let (Some(lo), Some(hi)) = (table_of(pointer), table_of(pointer + 1)) else {
+        return;
+    };
+    let Some(count) = window.iter().rev().find_map(|(opcode, value, _)| {
+        matches!(*opcode, CMP_IMMEDIATE | CPX_IMMEDIATE | CPY_IMMEDIATE).then_some(*value)
+    }) else {
+        log::debug!("JMP (${pointer:04X}) at L{user:06X} has no bounds check");
+        return;
+    };
+    if count == 0 || lo < 0x8000 || hi < 0x8000 {
+        return;
+    }
+
+    // a table of words is indexed by twice the number of the entry
+    let stride = if hi == lo + 1 { 2 } else { 1 };
+    let lo_label = cpu_to_label(id, lo, rom_data);
+    let hi_label = cpu_to_label(id, hi, rom_data);
+
+    let mut entries = 0;
+    for entry in 0..count {
+        let (Some(lo_offset), Some(hi_offset)) = (
+            prg_offset(lo_label + entry * stride, rom_data),
+            prg_offset(hi_label + entry * stride, rom_data),
+        ) else {
+            break;
+        };
+
+        let addr = (prg[hi_offset] as usize) << 8 | prg[lo_offset] as usize;
+        if addr < 0x8000 {
+            break;
+        }
+
+        cdl[lo_offset] |= 2;
+        cdl[hi_offset] |= 2;
+
+        let target = cpu_to_label(id, addr, rom_data);
+        annotations.labels.insert(target);
+        roots.push(target);
+        entries += 1;
+    }
+
+    if entries == 0 {
+        return;
+    }
+
+    log::debug!("Indirect jump table of {entries} entries at L{lo_label:06X}/L{hi_label:06X}");
+    annotations.labels.insert(user);
+    let user = options.label(user, DataLabels::default());
+    let tables = if stride == 2 {
+        vec![(lo_label, "words")]
+    } else {
+        vec![(lo_label, "low bytes"), (hi_label, "high bytes")]
+    };
+    for (label, kind) in tables {
+        annotations.labels.insert(label);
+        let last = (label + (entries - 1) * stride + stride - 1) & 0xFFFF;
+        let comment = format!(
+            "jump table {}-{} ({kind}, {entries} entries) used by JMP ({}) at {user}",
+            options.hex(label & 0xFFFF, 4),
+            options.hex(last, 4),
+            options.hex(pointer, 4)
+        );
+        annotations.comments.entry(label).or_default().push(comment);
+    }
+}
+
+pub(crate) fn read_word(prg: &[u8], offset: usize) -> usize {
+    (prg[offset + 1] as usize) << 8 | prg[offset] as usize
+}
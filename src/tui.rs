@@ -0,0 +1,241 @@
+use crate::BankResult;
+use crate::DataLabels;
+use crate::Location;
+use crate::Options;
+use crate::disassemble_with_hook;
+use crate::merge_cdls;
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use ratatui::crossterm::event;
+use ratatui::crossterm::event::Event;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::style::Style;
+use ratatui::widgets::List;
+use ratatui::widgets::ListState;
+use ratatui::widgets::Paragraph;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+
+const HELP: &str = "↑/↓ move  Enter follow  Backspace back  g go to  q quit";
+
+/// A line of the listing: an instruction, or data as written in the bank.
+struct Row {
+    position: usize,
+    text: String,
+    target: Option<usize>,
+}
+
+/// Disassembles the ROM `filename` in memory and browses it in the terminal.
+/// The disassembly is the one written without `--tui`, the files being
+/// dropped.
+pub fn browse(
+    filename: &str,
+    cdls: &[String],
+    options: &Options,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data = merge_cdls(cdls)?;
+    let rom = fs::read(filename)?;
+
+    let mut banks = vec![];
+    disassemble_with_hook(
+        &rom,
+        &data,
+        options,
+        &mut io::sink(),
+        |_| Ok(Box::new(io::sink())),
+        &mut |_, bank| banks.push(bank.clone()),
+    )?;
+    let (rows, labels) = listing(&banks);
+
+    let mut terminal = ratatui::init();
+    let result = Browser::new(rows, labels, options).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+/// The rows of the lines of the banks having a position, with the labels.
+fn listing(banks: &[BankResult]) -> (Vec<Row>, HashSet<usize>) {
+    let mut rows = vec![];
+    let mut labels = HashSet::new();
+    for bank in banks {
+        labels.extend(&bank.labels);
+        rows.extend(bank.lines.iter().filter_map(|line| {
+            Some(Row {
+                position: line.position?,
+                text: line.text.trim().to_string(),
+                target: line.target,
+            })
+        }));
+    }
+
+    (rows, labels)
+}
+
+struct Browser<'a> {
+    rows: Vec<Row>,
+    labels: HashSet<usize>,
+    rows_by_position: HashMap<usize, usize>,
+    options: &'a Options,
+    selected: usize,
+    offset: usize,
+    history: Vec<usize>,
+    /// the location being typed after `g`
+    input: Option<String>,
+    status: String,
+}
+
+impl<'a> Browser<'a> {
+    fn new(rows: Vec<Row>, labels: HashSet<usize>, options: &'a Options) -> Self {
+        // the first row of a position, the others continuing it
+        let mut rows_by_position = HashMap::new();
+        for (index, row) in rows.iter().enumerate() {
+            rows_by_position.entry(row.position).or_insert(index);
+        }
+        Browser {
+            rows,
+            labels,
+            rows_by_position,
+            options,
+            selected: 0,
+            offset: 0,
+            history: vec![],
+            input: None,
+            status: HELP.to_string(),
+        }
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if let Some(input) = &mut self.input {
+                match key.code {
+                    KeyCode::Char(c) => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Esc => self.input = None,
+                    KeyCode::Enter => {
+                        let input = self.input.take().unwrap_or_default();
+                        self.go_to_input(&input);
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            let page = terminal.size()?.height.saturating_sub(1) as usize;
+            let last = self.rows.len().saturating_sub(1);
+            match key.code {
+                KeyCode::Char('q') => return Ok(()),
+                KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+                KeyCode::PageUp => self.selected = self.selected.saturating_sub(page),
+                KeyCode::PageDown => self.selected = (self.selected + page).min(last),
+                KeyCode::Home => self.selected = 0,
+                KeyCode::End => self.selected = last,
+                KeyCode::Enter => {
+                    if let Some(target) = self.rows.get(self.selected).and_then(|row| row.target) {
+                        self.go_to(target);
+                    }
+                }
+                KeyCode::Backspace | KeyCode::Esc => {
+                    if let Some(previous) = self.history.pop() {
+                        self.selected = previous;
+                    }
+                }
+                KeyCode::Char('g') => self.input = Some(String::new()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Jumps to a label (`L01C000`) or a location (`$C000`, `1:0000`), an
+    /// address going to the first bank disassembled there.
+    fn go_to_input(&mut self, input: &str) {
+        let positions = || self.rows.iter().map(|row| row.position);
+        let label = match input.strip_prefix('L') {
+            Some(digits) => usize::from_str_radix(digits, 16).ok(),
+            None => match input.parse::<Location>() {
+                Ok(Location::Cpu(addr)) => positions().find(|p| p & 0xFFFF == addr as usize),
+                // the first row of a bank is at its start
+                Ok(Location::Bank(id, offset)) => positions()
+                    .find(|p| p >> 16 == id as usize)
+                    .map(|start| start + offset as usize),
+                Err(_) => None,
+            },
+        };
+
+        match label {
+            Some(label) => self.go_to(label),
+            None => self.status = format!("invalid location '{input}'"),
+        }
+    }
+
+    fn go_to(&mut self, label: usize) {
+        match self.rows_by_position.get(&label) {
+            Some(&index) => {
+                self.history.push(self.selected);
+                self.selected = index;
+                self.status = HELP.to_string();
+            }
+            None => {
+                self.status = format!(
+                    "{} isn't disassembled",
+                    self.options.label(label, DataLabels::default())
+                )
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [listing, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+
+        // only the visible rows are rendered
+        let height = listing.height as usize;
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if height > 0 && self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+
+        let items = self.rows.iter().skip(self.offset).take(height).map(|row| {
+            let label = if self.labels.contains(&row.position) {
+                format!(
+                    "{}:",
+                    self.options.label(row.position, DataLabels::default())
+                )
+            } else {
+                String::new()
+            };
+            format!(
+                "{:02X}:{:04X}  {label:9} {}",
+                row.position >> 16,
+                row.position & 0xFFFF,
+                row.text
+            )
+        });
+        let list = List::new(items).highlight_style(Style::new().reversed());
+        let mut state = ListState::default().with_selected(Some(self.selected - self.offset));
+        frame.render_stateful_widget(list, listing, &mut state);
+
+        let status_text = match &self.input {
+            Some(input) => format!("go to: {input}"),
+            None => self.status.clone(),
+        };
+        frame.render_widget(Paragraph::new(status_text), status);
+    }
+}
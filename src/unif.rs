@@ -0,0 +1,125 @@
+use crate::BANK_SIZE;
+use crate::CHR_SIZE;
+use std::io::Error;
+use std::io::ErrorKind;
+
+const HEADER_SIZE: usize = 32;
+
+/// Converts a UNIF image to the equivalent iNES one, the PRG and CHR chunks
+/// being concatenated in order and the board name giving the mapper.
+pub(crate) fn to_ines(data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if data.len() < HEADER_SIZE || !data.starts_with(b"UNIF") {
+        return Err(invalid("This file is not a UNIF ROM."));
+    }
+
+    let mut prg_chunks = vec![None; 16];
+    let mut chr_chunks = vec![None; 16];
+    let mut board = String::new();
+    let mut mirroring = 0;
+    let mut battery = false;
+
+    let mut position = HEADER_SIZE;
+    while position + 8 <= data.len() {
+        let id = &data[position..position + 4];
+        let length = u32::from_le_bytes(data[position + 4..position + 8].try_into()?) as usize;
+        let Some(chunk) = data.get(position + 8..position + 8 + length) else {
+            return Err(invalid("A UNIF chunk is truncated."));
+        };
+
+        match id {
+            [b'P', b'R', b'G', n] => prg_chunks[chunk_index(*n)?] = Some(chunk),
+            [b'C', b'H', b'R', n] => chr_chunks[chunk_index(*n)?] = Some(chunk),
+            b"MAPR" => {
+                let name = chunk.split(|b| *b == 0).next().unwrap_or_default();
+                board = String::from_utf8_lossy(name).into_owned();
+            }
+            b"MIRR" => mirroring = chunk.first().copied().unwrap_or(0),
+            b"BATR" => battery = true,
+            _ => log::debug!("Skipping the UNIF chunk {}", String::from_utf8_lossy(id)),
+        }
+
+        position += 8 + length;
+    }
+
+    let mut prg: Vec<u8> = prg_chunks
+        .into_iter()
+        .flatten()
+        .flatten()
+        .copied()
+        .collect();
+    let mut chr: Vec<u8> = chr_chunks
+        .into_iter()
+        .flatten()
+        .flatten()
+        .copied()
+        .collect();
+    if prg.is_empty() {
+        return Err(invalid("The UNIF ROM has no PRG chunk."));
+    }
+    prg.resize(prg.len().div_ceil(BANK_SIZE) * BANK_SIZE, 0);
+    chr.resize(chr.len().div_ceil(CHR_SIZE) * CHR_SIZE, 0);
+
+    let mapper = board_mapper(&board).unwrap_or_else(|| {
+        log::warn!("Unknown UNIF board {board:?}, assuming mapper 0.");
+        0
+    });
+    log::info!("UNIF board {board:?} is mapper {mapper}");
+
+    let mut flags_06 = (mapper & 0x0F) << 4;
+    match mirroring {
+        1 => flags_06 |= 0x01,
+        4 => flags_06 |= 0x08,
+        _ => {}
+    }
+    if battery {
+        flags_06 |= 0x02;
+    }
+
+    let mut ines = vec![
+        b'N',
+        b'E',
+        b'S',
+        0x1A,
+        (prg.len() / BANK_SIZE) as u8,
+        (chr.len() / CHR_SIZE) as u8,
+        flags_06,
+        mapper & 0xF0,
+    ];
+    ines.resize(16, 0);
+    ines.extend(prg);
+    ines.extend(chr);
+    Ok(ines)
+}
+
+/// The chunks are numbered by a hexadecimal digit.
+fn chunk_index(digit: u8) -> Result<usize, Box<dyn std::error::Error>> {
+    (digit as char)
+        .to_digit(16)
+        .map(|n| n as usize)
+        .ok_or_else(|| invalid("Invalid UNIF chunk number."))
+}
+
+/// Returns the iNES mapper of the common boards, named without their prefix
+/// (`NES-`, `HVC-`, `UNL-`, ...).
+fn board_mapper(board: &str) -> Option<u8> {
+    let name = board.split_once('-').map_or(board, |(_, name)| name);
+    let mapper = match name {
+        _ if name.starts_with("NROM") => 0,
+        "SAROM" | "SBROM" | "SCROM" | "SEROM" | "SFROM" | "SGROM" | "SHROM" | "SJROM" | "SKROM"
+        | "SLROM" | "SNROM" | "SOROM" | "SUROM" | "SXROM" => 1,
+        "UNROM" | "UOROM" => 2,
+        "CNROM" => 3,
+        "TBROM" | "TEROM" | "TFROM" | "TGROM" | "TKROM" | "TLROM" | "TNROM" | "TR1ROM"
+        | "TSROM" | "TVROM" => 4,
+        "EKROM" | "ELROM" | "ETROM" | "EWROM" => 5,
+        "AMROM" | "ANROM" | "AN1ROM" | "AOROM" => 7,
+        "PNROM" => 9,
+        "FJROM" | "FKROM" => 10,
+        _ => return None,
+    };
+    Some(mapper)
+}
+
+fn invalid(message: &str) -> Box<dyn std::error::Error> {
+    Box::new(Error::new(ErrorKind::InvalidInput, message))
+}
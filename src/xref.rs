@@ -0,0 +1,57 @@
+use crate::Addressing;
+use crate::Annotations;
+use crate::DataLabels;
+use crate::Options;
+use crate::RomData;
+use crate::decode_one;
+use crate::offset_to_label;
+use crate::prg_offset;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+/// `--data-xref`: comments the data read by the code with the instructions
+/// reading it, e.g. `; read by L00C123, L00C140`.
+pub(crate) fn annotate_data_reads(
+    prg: &[u8],
+    cdl: &[u8],
+    rom_data: RomData,
+    options: &Options,
+    annotations: &mut Annotations,
+) {
+    let mut readers: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+    let mut offset = 0;
+    while offset < prg.len() {
+        let position = offset_to_label(offset, rom_data);
+        let Some(instruction) = decode_one(prg, position, rom_data, DataLabels::default(), options)
+            .filter(|_| (cdl[offset] & 1) == 1)
+        else {
+            offset += 1;
+            continue;
+        };
+
+        // the jumps and the calls go to code
+        let name = instruction.opcode.name;
+        let jumps = instruction.opcode.addressing == Addressing::Relative
+            || name == "JSR"
+            || (name == "JMP" && instruction.opcode.addressing == Addressing::Absolute);
+        if let Some(target) = instruction.rom_target().filter(|_| !jumps)
+            && prg_offset(target, rom_data).is_some_and(|target| (cdl[target] & 1) == 0)
+        {
+            readers.entry(target).or_default().insert(position);
+        }
+        offset += instruction.size + 1;
+    }
+
+    for (target, readers) in readers {
+        log::debug!("L{target:06X} is read by {} instruction(s)", readers.len());
+        let readers: Vec<String> = readers
+            .iter()
+            .map(|r| options.label(*r, DataLabels::default()))
+            .collect();
+        annotations
+            .comments
+            .entry(target)
+            .or_default()
+            .push(format!("read by {}", readers.join(", ")));
+    }
+}
@@ -0,0 +1,98 @@
+//! Disassembles a small ROM and, when WLA DX is installed, assembles the
+//! output back to check it gives the original ROM.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use nes_disasm::Options;
+
+const BANK_SIZE: usize = 0x4000;
+const CHR_SIZE: usize = 0x2000;
+
+/// An NROM game: a reset routine writing a table to the PPU, then looping.
+fn rom() -> Vec<u8> {
+    let header = *b"NES\x1A\x02\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+    let mut prg = vec![0u8; 2 * BANK_SIZE];
+    let code = [
+        0xA2, 0x00, // LDX #0
+        0xBD, 0x10, 0xC0, // LDA $C010,X
+        0x8D, 0x07, 0x20, // STA $2007
+        0xE8, // INX
+        0xE0, 0x04, // CPX #4
+        0xD0, 0xF5, // BNE $C002
+        0x4C, 0x0D, 0xC0, // JMP $C00D
+    ];
+    prg[0x4000..0x4000 + code.len()].copy_from_slice(&code);
+    prg[0x4010..0x4014].copy_from_slice(&[0x0F, 0x16, 0x27, 0x30]);
+    prg[0x7FFA..].copy_from_slice(&[0x0D, 0xC0, 0x00, 0xC0, 0x0D, 0xC0]);
+    let chr: Vec<u8> = (0..CHR_SIZE).map(|i| i as u8).collect();
+    [&header[..], &prg, &chr].concat()
+}
+
+/// The CDL of `rom`: the code and the table read by it.
+fn cdl() -> Vec<u8> {
+    let mut cdl = vec![0u8; 2 * BANK_SIZE + CHR_SIZE];
+    cdl[0x4000..0x4010].fill(0x01);
+    cdl[0x4010..0x4014].fill(0x02);
+    cdl
+}
+
+/// A directory of its own under the temporary one, emptied.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nes-disasm-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn installed(program: &str) -> bool {
+    Command::new(program).arg("-h").output().is_ok()
+}
+
+fn run(program: &str, args: &[&str], dir: &Path) {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{program} failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn reassemble() {
+    let dir = temp_dir("reassemble");
+    let (rom_path, cdl_path, output) =
+        (dir.join("rom.nes"), dir.join("rom.cdl"), dir.join("output"));
+    fs::write(&rom_path, rom()).unwrap();
+    fs::write(&cdl_path, cdl()).unwrap();
+
+    nes_disasm::disassemble(
+        rom_path.to_str().unwrap(),
+        &[cdl_path.to_str().unwrap().to_string()],
+        output.to_str().unwrap(),
+        &Options::new(),
+    )
+    .unwrap();
+    assert!(output.join("main.s").exists());
+
+    if !installed("wla-6502") || !installed("wlalink") {
+        eprintln!("wla-6502 or wlalink not found, the output is not reassembled");
+        fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+    run("wla-6502", &["-o", "main.o", "main.s"], &output);
+    fs::write(output.join("linkfile"), "[objects]\nmain.o\n").unwrap();
+    run("wlalink", &["linkfile", "rom.nes"], &output);
+    assert!(
+        fs::read(output.join("rom.nes")).unwrap() == rom(),
+        "the reassembled ROM differs from the original"
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}